@@ -1,37 +1,206 @@
 use std::{
+    cmp::Ordering,
     fs::File,
-    io::{BufReader, Read},
+    io::{BufRead, BufReader, BufWriter, Write},
 };
 
 fn main() {
-    let path = std::env::args()
-        .nth(1)
-        .expect("No input file path provided");
+    let mut path = None;
+    let mut trails = false;
+    let mut render = false;
+    let mut limits = Limits::default();
+    let mut rules = Rules::default();
+    let mut blockers = Vec::new();
+    let mut heatmap = None;
+    let mut heatmap_term = false;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trails" => trails = true,
+            "--render" => render = true,
+            "--limit" => {
+                let limit = args.next().expect("No limit provided");
+                limits.per_trailhead = Some(limit.parse().expect("Cannot parse limit"));
+            }
+            "--max-trails" => {
+                let limit = args.next().expect("No limit provided");
+                limits.total = Some(limit.parse().expect("Cannot parse limit"));
+            }
+            "--delta" => {
+                let deltas = args.next().expect("No height changes provided");
+                rules.deltas = Rules::parse_deltas(&deltas).expect("Cannot parse height changes");
+            }
+            "--diagonal" => rules.diagonal = true,
+            "--heatmap" => heatmap = Some(args.next().expect("No heatmap path provided")),
+            "--heatmap-term" => heatmap_term = true,
+            "--blockers" => blockers.extend(args.next().expect("No blockers provided").bytes()),
+            "--start" => {
+                let start = args.next().expect("No start height provided");
+                rules.start = start.parse().expect("Cannot parse start height");
+            }
+            "--end" => {
+                let end = args.next().expect("No end height provided");
+                rules.end = end.parse().expect("Cannot parse end height");
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("Unexpected argument: {arg}"),
+        }
+    }
+
+    let path = path.expect("No input file path provided");
     let file = File::open(path).expect("Cannot open file");
     let reader = BufReader::new(file);
 
-    let sum = process(reader);
+    let mut grid = parse(reader, &blockers);
+    grid.rules = rules;
+    let starts = grid.starts();
+
+    if trails || render {
+        let sweep = grid.sweep();
+
+        for (start, trailhead) in starts.iter().zip(grid.trails(&starts, limits)) {
+            println!(
+                "Trailhead {:?}: score {}, rating {}",
+                trailhead.start,
+                sweep.score(*start),
+                sweep.rating(*start)
+            );
+
+            for trail in &trailhead.trails {
+                let path = trail
+                    .iter()
+                    .map(|(x, y)| format!("({x}, {y})"))
+                    .collect::<Vec<_>>();
+                println!("{}", path.join(" -> "));
+
+                if render {
+                    println!("{}", grid.render(trail));
+                }
+            }
+        }
+    }
+
+    if heatmap.is_some() || heatmap_term {
+        let traffic = grid.traffic();
+
+        if heatmap_term {
+            println!("{}", render_heatmap(&grid, &traffic));
+        }
+
+        if let Some(path) = heatmap {
+            let file = File::create(path).expect("Cannot create heatmap file");
+            write_ppm(&grid, &traffic, BufWriter::new(file)).expect("Cannot write heatmap");
+        }
+    }
+
+    let sum = process(&grid);
 
     println!("{sum}");
 }
 
-fn process(reader: impl Read) -> usize {
+/// Side length in pixels of a cell in the heatmap image.
+const HEATMAP_SCALE: usize = 8;
+const WALL_COLOR: [u8; 3] = [48, 48, 48];
+
+/// Maps a share of the busiest cell's traffic onto a black, blue, red,
+/// yellow, white ramp.
+fn heat_color(fraction: f64) -> [u8; 3] {
+    const STOPS: [[f64; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [0.0, 0.0, 160.0],
+        [200.0, 0.0, 0.0],
+        [255.0, 200.0, 0.0],
+        [255.0, 255.0, 255.0],
+    ];
+
+    let position = fraction.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let i = (position as usize).min(STOPS.len() - 2);
+    let t = position - i as f64;
+
+    std::array::from_fn(|c| (STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * t).round() as u8)
+}
+
+fn cell_colors(grid: &Grid, traffic: &[usize]) -> Vec<[u8; 3]> {
+    let max = traffic.iter().copied().max().unwrap_or(0).max(1) as f64;
+
+    grid.grid
+        .iter()
+        .zip(traffic)
+        .map(|(height, traffic)| match *height {
+            WALL => WALL_COLOR,
+            _ => heat_color(*traffic as f64 / max),
+        })
+        .collect()
+}
+
+/// Writes the heatmap as a binary PPM image.
+fn write_ppm(grid: &Grid, traffic: &[usize], mut writer: impl Write) -> std::io::Result<()> {
+    let colors = cell_colors(grid, traffic);
+    let rows = grid.grid.len() / grid.columns;
+
+    writeln!(
+        writer,
+        "P6\n{} {}\n255",
+        grid.columns * HEATMAP_SCALE,
+        rows * HEATMAP_SCALE
+    )?;
+
+    for row in colors.chunks(grid.columns) {
+        for _ in 0..HEATMAP_SCALE {
+            for color in row {
+                for _ in 0..HEATMAP_SCALE {
+                    writer.write_all(color)?;
+                }
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+/// Draws the map's heights over a background graded by traffic, using
+/// 24-bit terminal colours.
+fn render_heatmap(grid: &Grid, traffic: &[usize]) -> String {
+    let colors = cell_colors(grid, traffic);
+
+    let rows = colors
+        .chunks(grid.columns)
+        .zip(grid.grid.chunks(grid.columns));
+    rows.map(|(colors, heights)| {
+        let mut line = String::new();
+        for ([r, g, b], height) in colors.iter().zip(heights) {
+            let cell = match *height {
+                WALL => '#',
+                height => (b'0' + height) as char,
+            };
+            // Dark text on the bright end of the ramp
+            let fg = if *r as u32 + *g as u32 > 400 { 30 } else { 97 };
+            line.push_str(&format!("\x1b[{fg};48;2;{r};{g};{b}m{cell}"));
+        }
+        line.push_str("\x1b[0m");
+        line
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn process(grid: &Grid) -> usize {
+    grid.solve(&grid.starts())
+}
+
+/// Reads the topographic map with the puzzle's trail rules, treating `.` and
+/// any of `blockers` as an impassable cell.
+fn parse(reader: impl BufRead, blockers: &[u8]) -> Grid {
     let bytes = reader.bytes().map(|byte| byte.expect("Cannot read byte"));
 
     let mut grid = Vec::new();
     let mut offset = 0;
     let mut columns = None;
-    let mut zeroes = Vec::new();
 
     for byte in bytes {
         match byte {
-            b'0' => {
-                grid.push(0);
-                zeroes.push(offset);
-                offset += 1;
-            }
-
-            b'1'..=b'9' => {
+            b'0'..=b'9' => {
                 grid.push(byte - b'0');
                 offset += 1;
             }
@@ -42,25 +211,175 @@ fn process(reader: impl Read) -> usize {
 
             b'\r' => {}
 
+            b'.' => {
+                grid.push(WALL);
+                offset += 1;
+            }
+
+            _ if blockers.contains(&byte) => {
+                grid.push(WALL);
+                offset += 1;
+            }
+
             _ => panic!("Invalid byte"),
         }
     }
 
-    let grid = Grid {
-        columns: columns.expect("No columns found"),
+    Grid {
+        columns: columns.unwrap_or(offset),
         grid,
-    };
+        rules: Rules::default(),
+    }
+}
+
+type Point = (usize, usize);
+
+/// The height stored for impassable cells, which no trail can step onto.
+const WALL: u8 = u8::MAX;
+
+/// Caps on how many trails are listed, so maps with a huge rating can still
+/// be inspected.
+#[derive(Clone, Copy, Debug, Default)]
+struct Limits {
+    per_trailhead: Option<usize>,
+    total: Option<usize>,
+}
+
+/// The trails starting from one trailhead, each from its start height to its
+/// end height.
+#[derive(Debug, PartialEq, Eq)]
+struct Trailhead {
+    start: Point,
+    trails: Vec<Vec<Point>>,
+}
+
+/// What counts as a hiking trail. The puzzle's trails climb by exactly one
+/// per orthogonal step from 0 to 9.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Rules {
+    /// The height changes allowed for a single step.
+    deltas: Vec<i8>,
+    /// Whether diagonal steps are allowed as well as orthogonal ones.
+    diagonal: bool,
+    start: u8,
+    end: u8,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            deltas: vec![1],
+            diagonal: false,
+            start: 0,
+            end: 9,
+        }
+    }
+}
+
+impl Rules {
+    /// Parses a comma-separated list of height changes, each either a single
+    /// change like `1` or `-2`, both signs of one like `±1`, or a range like
+    /// `1..=2`.
+    fn parse_deltas(spec: &str) -> Result<Vec<i8>, &'static str> {
+        let parse = |delta: &str| delta.parse::<i8>().map_err(|_| "Invalid height change");
+        let mut deltas = Vec::new();
+
+        for item in spec.split(',').map(str::trim) {
+            if let Some(delta) = item.strip_prefix('±').or(item.strip_prefix("+-")) {
+                let delta = parse(delta)?;
+                deltas.extend([-delta, delta]);
+            } else if let Some((from, to)) = item.split_once("..=") {
+                deltas.extend(parse(from)?..=parse(to)?);
+            } else if let Some((from, to)) = item.split_once("..") {
+                deltas.extend(parse(from)?..parse(to)?);
+            } else {
+                deltas.push(parse(item)?);
+            }
+        }
+
+        deltas.sort_unstable();
+        deltas.dedup();
+        Ok(deltas)
+    }
+
+    fn dirs(&self) -> &'static [Dir] {
+        match self.diagonal {
+            true => &Dir::ALL,
+            false => &Dir::ORTHOGONAL,
+        }
+    }
+
+    fn allows(&self, from: u8, to: u8) -> bool {
+        self.deltas.contains(&(to as i8 - from as i8))
+    }
+
+    /// Whether every step climbs or every step descends, so no trail can
+    /// ever revisit a height.
+    fn direction(&self) -> Option<Ordering> {
+        if self.deltas.iter().all(|delta| *delta > 0) {
+            Some(Ordering::Greater)
+        } else if self.deltas.iter().all(|delta| *delta < 0) {
+            Some(Ordering::Less)
+        } else {
+            None
+        }
+    }
+}
 
-    grid.solve(&zeroes)
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Dir {
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Dir {
+    const ORTHOGONAL: [Self; 4] = [Dir::Up, Dir::Down, Dir::Left, Dir::Right];
+    const ALL: [Self; 8] = [
+        Dir::Up,
+        Dir::Down,
+        Dir::Left,
+        Dir::Right,
+        Dir::UpLeft,
+        Dir::UpRight,
+        Dir::DownLeft,
+        Dir::DownRight,
+    ];
+
+    const fn delta(&self) -> (isize, isize) {
+        match self {
+            Dir::Up => (0, -1),
+            Dir::Down => (0, 1),
+            Dir::Left => (-1, 0),
+            Dir::Right => (1, 0),
+            Dir::UpLeft => (-1, -1),
+            Dir::UpRight => (1, -1),
+            Dir::DownLeft => (-1, 1),
+            Dir::DownRight => (1, 1),
+        }
+    }
+
+    fn apply(&self, grid: &Grid, offset: usize) -> Option<usize> {
+        let (x, y) = grid.offset_to_point(offset).expect("Invalid offset");
+        let (dx, dy) = self.delta();
+        let point = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+        grid.point_to_offset(point)
+    }
 }
 
 struct Grid {
     columns: usize,
     grid: Vec<u8>,
+    rules: Rules,
 }
 
 impl Grid {
-    fn offset_to_point(&self, offset: usize) -> Option<(usize, usize)> {
+    fn offset_to_point(&self, offset: usize) -> Option<Point> {
         match offset {
             offset if offset < self.grid.len() => {
                 Some((offset % self.columns, offset / self.columns))
@@ -69,106 +388,256 @@ impl Grid {
         }
     }
 
-    fn point_to_offset(&self, point: (usize, usize)) -> Option<usize> {
+    fn point_to_offset(&self, point: Point) -> Option<usize> {
         match point.1 * self.columns + point.0 {
-            offset if offset < self.grid.len() => Some(offset),
+            offset if point.0 < self.columns && offset < self.grid.len() => Some(offset),
             _ => None,
         }
     }
 
-    fn solve(&self, starts: &[usize]) -> usize {
-        #[derive(Clone, Copy, PartialEq, Eq)]
-        enum Dir {
-            Up,
-            Down,
-            Left,
-            Right,
-        }
-
-        impl Dir {
-            fn apply(&self, grid: &Grid, offset: usize) -> Option<usize> {
-                let (x, y) = grid.offset_to_point(offset).expect("Invalid offset");
-                if x == 0 && self == &Dir::Left || y == 0 && self == &Dir::Up {
-                    return None;
+    /// The offsets of every cell at the start height.
+    fn starts(&self) -> Vec<usize> {
+        self.offsets(self.rules.start)
+    }
+
+    fn offsets(&self, height: u8) -> Vec<usize> {
+        (0..self.grid.len())
+            .filter(|offset| self.grid[*offset] == height)
+            .collect()
+    }
+
+    /// The cells a trail can step to from `offset`.
+    fn neighbours(&self, offset: usize) -> impl '_ + Iterator<Item = usize> {
+        self.rules
+            .dirs()
+            .iter()
+            .filter_map(move |dir| dir.apply(self, offset))
+            .filter(|next| self.grid[*next] != WALL)
+            .filter(move |next| self.rules.allows(self.grid[offset], self.grid[*next]))
+    }
+
+    /// Every distinct trail from each of `starts`, as the points it visits,
+    /// stopping early once a limit is reached. A trail never visits a cell
+    /// twice, and ends as soon as it reaches the end height.
+    fn trails(&self, starts: &[usize], limits: Limits) -> Vec<Trailhead> {
+        fn walk(
+            grid: &Grid,
+            offset: usize,
+            path: &mut Vec<Point>,
+            visited: &mut [bool],
+            trails: &mut Vec<Vec<Point>>,
+            limit: usize,
+        ) {
+            path.push(grid.offset_to_point(offset).expect("Invalid offset"));
+            visited[offset] = true;
+
+            if grid.grid[offset] == grid.rules.end {
+                trails.push(path.clone());
+            } else {
+                for next in grid.neighbours(offset) {
+                    if trails.len() >= limit {
+                        break;
+                    }
+
+                    if !visited[next] {
+                        walk(grid, next, path, visited, trails, limit);
+                    }
                 }
+            }
 
-                let x = match self {
-                    Dir::Left => x - 1,
-                    Dir::Right => x + 1,
-                    _ => x,
-                };
+            visited[offset] = false;
+            path.pop();
+        }
 
-                let y = match self {
-                    Dir::Up => y - 1,
-                    Dir::Down => y + 1,
-                    _ => y,
-                };
+        let mut remaining = limits.total.unwrap_or(usize::MAX);
+        let mut visited = vec![false; self.grid.len()];
+        let mut trailheads = Vec::new();
 
-                grid.point_to_offset((x, y))
+        for start in starts {
+            let limit = limits.per_trailhead.unwrap_or(usize::MAX).min(remaining);
+            let mut trails = Vec::new();
+            walk(
+                self,
+                *start,
+                &mut Vec::new(),
+                &mut visited,
+                &mut trails,
+                limit,
+            );
+
+            remaining -= trails.len();
+            trailheads.push(Trailhead {
+                start: self.offset_to_point(*start).expect("Invalid offset"),
+                trails,
+            });
+        }
+
+        trailheads
+    }
+
+    /// Draws the map with only the heights along `trail` shown, and
+    /// impassable cells as `#`.
+    fn render(&self, trail: &[Point]) -> String {
+        let mut out = self
+            .grid
+            .iter()
+            .map(|height| if *height == WALL { b'#' } else { b'.' })
+            .collect::<Vec<_>>();
+        for point in trail {
+            let offset = self.point_to_offset(*point).expect("Invalid point");
+            out[offset] = b'0' + self.grid[offset];
+        }
+
+        out.chunks(self.columns)
+            .map(|row| String::from_utf8_lossy(row).into_owned())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn solve(&self, starts: &[usize]) -> usize {
+        let sweep = self.sweep();
+        starts.iter().map(|start| sweep.score(*start)).sum()
+    }
+
+    /// Works out every cell's rating and reachable summits in one pass, from
+    /// the summits back, as each cell only depends on the cells it can step
+    /// to, which are all a height closer to the end.
+    ///
+    /// That ordering only exists when every step climbs, or every step
+    /// descends. Otherwise trails can wander back and forth, and only the
+    /// trailheads' results are worked out, by searching every trail.
+    fn sweep(&self) -> Sweep {
+        let summits = self.offsets(self.rules.end);
+        let words = summits.len().div_ceil(64);
+
+        let mut sweep = Sweep {
+            ratings: vec![0; self.grid.len()],
+            summits: vec![0; self.grid.len() * words],
+            words,
+        };
+
+        for (i, offset) in summits.iter().enumerate() {
+            sweep.ratings[*offset] = 1;
+            sweep.summits[offset * words + i / 64] |= 1 << (i % 64);
+        }
+
+        let mut heights = [const { Vec::new() }; 10];
+        for (offset, height) in self.grid.iter().enumerate() {
+            if *height != self.rules.end && *height != WALL {
+                heights[*height as usize].push(offset);
+            }
+        }
+
+        match self.rules.direction() {
+            Some(Ordering::Greater) => heights.reverse(),
+            Some(_) => {}
+            None => {
+                self.search(&mut sweep);
+                return sweep;
             }
+        }
 
-            const fn next(&self) -> [Self; 3] {
-                match self {
-                    Dir::Up => [Dir::Left, Dir::Up, Dir::Right],
-                    Dir::Down => [Dir::Right, Dir::Down, Dir::Left],
-                    Dir::Left => [Dir::Down, Dir::Left, Dir::Up],
-                    Dir::Right => [Dir::Up, Dir::Right, Dir::Down],
-                }
+        for offset in heights.into_iter().flatten() {
+            for next in self.neighbours(offset) {
+                sweep.add(offset, next);
             }
         }
 
-        fn solve<const N: usize>(
-            grid: &Grid,
-            offset: usize,
-            dirs: &[Dir; N],
-            visited: &mut Vec<bool>,
-        ) -> usize {
-            if visited[offset] {
-                return 0;
+        sweep
+    }
+
+    /// Fills in the sweep for the trailheads only, by walking every trail.
+    fn search(&self, sweep: &mut Sweep) {
+        fn walk(grid: &Grid, start: usize, offset: usize, visited: &mut [bool], sweep: &mut Sweep) {
+            if grid.grid[offset] == grid.rules.end {
+                sweep.add(start, offset);
+                return;
             }
 
             visited[offset] = true;
+            for next in grid.neighbours(offset) {
+                if !visited[next] {
+                    walk(grid, start, next, visited, sweep);
+                }
+            }
+            visited[offset] = false;
+        }
 
-            let value = grid.grid[offset];
-            let mut sum = 0;
+        let mut visited = vec![false; self.grid.len()];
 
-            for dir in dirs {
-                let offset = match dir.apply(grid, offset) {
-                    Some(offset) => offset,
-                    None => continue,
-                };
+        for start in self.starts() {
+            if self.rules.start != self.rules.end {
+                walk(self, start, start, &mut visited, sweep);
+            }
+        }
+    }
 
-                let next = grid.grid[offset];
+    /// How many distinct trails pass through each cell, found by walking
+    /// every trail from every trailhead.
+    fn traffic(&self) -> Vec<usize> {
+        fn walk(
+            grid: &Grid,
+            offset: usize,
+            path: &mut Vec<usize>,
+            visited: &mut [bool],
+            traffic: &mut [usize],
+        ) {
+            path.push(offset);
+            visited[offset] = true;
 
-                if next != value + 1 {
-                    continue;
+            if grid.grid[offset] == grid.rules.end {
+                for cell in path.iter() {
+                    traffic[*cell] += 1;
                 }
-
-                if value == 8 {
-                    if next == 9 && !visited[offset] {
-                        visited[offset] = true;
-                        sum += 1;
+            } else {
+                for next in grid.neighbours(offset) {
+                    if !visited[next] {
+                        walk(grid, next, path, visited, traffic);
                     }
-                } else {
-                    sum += solve(grid, offset, &dir.next(), visited);
                 }
             }
 
-            sum
+            visited[offset] = false;
+            path.pop();
         }
 
-        let mut sum = 0;
-        for start in starts {
-            let mut visited = vec![false; self.grid.len()];
-            sum += solve(
-                self,
-                *start,
-                const { &[Dir::Up, Dir::Down, Dir::Left, Dir::Right] },
-                &mut visited,
-            );
+        let mut traffic = vec![0; self.grid.len()];
+        let mut visited = vec![false; self.grid.len()];
+
+        for start in self.starts() {
+            walk(self, start, &mut Vec::new(), &mut visited, &mut traffic);
         }
 
-        sum
+        traffic
+    }
+}
+
+/// Per-cell results of [`Grid::sweep`].
+struct Sweep {
+    /// How many distinct trails lead from each cell to a summit.
+    ratings: Vec<usize>,
+    /// For each cell, `words` words of a bitset of the summits it reaches.
+    summits: Vec<u64>,
+    words: usize,
+}
+
+impl Sweep {
+    /// How many summits can be reached from the cell.
+    fn score(&self, offset: usize) -> usize {
+        let words = &self.summits[offset * self.words..(offset + 1) * self.words];
+        words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    fn rating(&self, offset: usize) -> usize {
+        self.ratings[offset]
+    }
+
+    /// Adds the trails and summits of `next` to those of `offset`.
+    fn add(&mut self, offset: usize, next: usize) {
+        self.ratings[offset] += self.ratings[next];
+        for word in 0..self.words {
+            self.summits[offset * self.words + word] |= self.summits[next * self.words + word];
+        }
     }
 }
 
@@ -176,23 +645,327 @@ impl Grid {
 mod tests {
     use super::*;
 
+    const EXAMPLE: &str = r"
+        89010123
+        78121874
+        87430965
+        96549874
+        45678903
+        32019012
+        01329801
+        10456732
+    ";
+
     #[test]
     fn test() {
-        let b = r"
-            89010123
-            78121874
-            87430965
-            96549874
-            45678903
-            32019012
-            01329801
-            10456732
+        let b = EXAMPLE.trim().replace(' ', "");
+
+        let sum = process(&parse(b.as_bytes(), b""));
+
+        assert_eq!(sum, 36);
+    }
+
+    #[test]
+    fn test_trails() {
+        let input = EXAMPLE.trim().replace(' ', "");
+        let grid = parse(input.as_bytes(), b"");
+        let zeroes = grid.starts();
+
+        let trailheads = grid.trails(&zeroes, Limits::default());
+        let trails = trailheads.iter().map(|t| t.trails.len());
+        assert_eq!(trails.sum::<usize>(), 81);
+        assert_eq!(trailheads[0].start, (2, 0));
+        assert_eq!(trailheads[0].trails.len(), 20);
+
+        for trail in trailheads.iter().flat_map(|t| &t.trails) {
+            assert_eq!(trail.len(), 10);
+            for (i, window) in trail.windows(2).enumerate() {
+                let (a, b) = (window[0], window[1]);
+                assert_eq!(a.0.abs_diff(b.0) + a.1.abs_diff(b.1), 1);
+                let offset = grid.point_to_offset(a).unwrap();
+                assert_eq!(grid.grid[offset], i as u8);
+            }
+        }
+
+        let limits = Limits {
+            per_trailhead: Some(3),
+            total: Some(10),
+        };
+        let trails = grid.trails(&zeroes, limits);
+        let counts = trails.iter().map(|t| t.trails.len()).collect::<Vec<_>>();
+        assert_eq!(counts, [3, 3, 3, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(trails[0].trails, trailheads[0].trails[..3]);
+    }
+
+    #[test]
+    fn test_sweep() {
+        let input = EXAMPLE.trim().replace(' ', "");
+        let grid = parse(input.as_bytes(), b"");
+        let zeroes = grid.starts();
+        let sweep = grid.sweep();
+
+        let scores = zeroes.iter().map(|start| sweep.score(*start));
+        let ratings = zeroes.iter().map(|start| sweep.rating(*start));
+        assert_eq!(scores.collect::<Vec<_>>(), [5, 6, 5, 3, 1, 3, 5, 3, 5]);
+        assert_eq!(ratings.collect::<Vec<_>>(), [20, 24, 10, 4, 1, 4, 5, 8, 5]);
+
+        // Every 9 is a summit reaching only itself
+        for (offset, height) in grid.grid.iter().enumerate() {
+            if *height == 9 {
+                assert_eq!((sweep.score(offset), sweep.rating(offset)), (1, 1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_many_summits() {
+        // More summits than fit in one bitset word
+        let input = format!(
+            "{}\n{}\n{}",
+            "9".repeat(100),
+            "8".repeat(100),
+            "9".repeat(100)
+        );
+        let grid = parse(input.as_bytes(), b"");
+        let sweep = grid.sweep();
+
+        assert_eq!(sweep.words, 4);
+        assert_eq!((sweep.score(180), sweep.rating(180)), (2, 2));
+        assert_eq!((sweep.score(199), sweep.rating(199)), (2, 2));
+        assert_eq!(grid.solve(&[]), 0);
+    }
+
+    #[test]
+    fn test_render() {
+        let input = r"
+            0123
+            1234
+            8765
+            9876
         "
         .trim()
         .replace(' ', "");
+        let grid = parse(input.as_bytes(), b"");
+        let zeroes = grid.starts();
+
+        let trailheads = grid.trails(&zeroes, Limits::default());
+        assert_eq!(trailheads.len(), 1);
+        assert_eq!(trailheads[0].trails.len(), 16);
+        assert_eq!(
+            grid.render(&trailheads[0].trails[0]),
+            "0...\n1234\n...5\n9876"
+        );
+    }
 
-        let sum = process(b.as_bytes());
+    #[test]
+    fn test_edges() {
+        // Stepping right off the end of a row must not wrap onto the next
+        let input = "0123\n4567\n8999";
+        let grid = parse(input.as_bytes(), b"");
+        let zeroes = grid.starts();
+        assert!(grid.trails(&zeroes, Limits::default())[0].trails.is_empty());
+        assert_eq!(grid.solve(&zeroes), 0);
+    }
 
-        assert_eq!(sum, 36);
+    #[test]
+    fn test_rules() {
+        let input = EXAMPLE.trim().replace(' ', "");
+
+        let count = |rules: Rules| {
+            let mut grid = parse(input.as_bytes(), b"");
+            grid.rules = rules;
+            let sweep = grid.sweep();
+            let starts = grid.starts();
+            let score = starts
+                .iter()
+                .map(|start| sweep.score(*start))
+                .sum::<usize>();
+            let rating = starts
+                .iter()
+                .map(|start| sweep.rating(*start))
+                .sum::<usize>();
+
+            let trails = grid.trails(&starts, Limits::default());
+            assert_eq!(trails.iter().map(|t| t.trails.len()).sum::<usize>(), rating);
+
+            (score, rating)
+        };
+
+        assert_eq!(count(Rules::default()), (36, 81));
+
+        let descending = Rules {
+            deltas: vec![-1],
+            start: 9,
+            end: 0,
+            ..Rules::default()
+        };
+        assert_eq!(count(descending), (36, 81));
+
+        let diagonal = Rules {
+            diagonal: true,
+            ..Rules::default()
+        };
+        assert_eq!(count(diagonal), (43, 93));
+
+        let deltas = Rules::parse_deltas("1..=2").unwrap();
+        assert_eq!(
+            count(Rules {
+                deltas,
+                ..Rules::default()
+            }),
+            (43, 88)
+        );
+
+        // Trails that can step back down are searched rather than swept
+        let deltas = Rules::parse_deltas("±1").unwrap();
+        assert_eq!(
+            count(Rules {
+                deltas,
+                ..Rules::default()
+            }),
+            (49, 1293)
+        );
+    }
+
+    #[test]
+    fn test_parse_deltas() {
+        assert_eq!(Rules::parse_deltas("1"), Ok(vec![1]));
+        assert_eq!(Rules::parse_deltas("±1"), Ok(vec![-1, 1]));
+        assert_eq!(Rules::parse_deltas("+-2, 1"), Ok(vec![-2, 1, 2]));
+        assert_eq!(Rules::parse_deltas("1..=3"), Ok(vec![1, 2, 3]));
+        assert_eq!(Rules::parse_deltas("-2..0,1..=1"), Ok(vec![-2, -1, 1]));
+        assert_eq!(Rules::parse_deltas("x"), Err("Invalid height change"));
+    }
+
+    #[test]
+    fn test_impassable() {
+        let totals = |input: &str| {
+            let grid = parse(input.trim().replace(' ', "").as_bytes(), b"");
+            let sweep = grid.sweep();
+            let starts = grid.starts();
+            let score = starts
+                .iter()
+                .map(|start| sweep.score(*start))
+                .sum::<usize>();
+            let rating = starts
+                .iter()
+                .map(|start| sweep.rating(*start))
+                .sum::<usize>();
+            (score, rating)
+        };
+
+        let input = r"
+            ...0...
+            ...1...
+            ...2...
+            6543456
+            7.....7
+            8.....8
+            9.....9
+        ";
+        assert_eq!(totals(input), (2, 2));
+
+        let input = r"
+            10..9..
+            2...8..
+            3...7..
+            4567654
+            ...8..3
+            ...9..2
+            .....01
+        ";
+        assert_eq!(totals(input).0, 3);
+
+        let input = r"
+            .....0.
+            ..4321.
+            ..5..2.
+            ..6543.
+            ..7..4.
+            ..8765.
+            ..9....
+        ";
+        assert_eq!(totals(input).1, 3);
+
+        let input = r"
+            ..90..9
+            ...1.98
+            ...2..7
+            6543456
+            765.987
+            876....
+            987....
+        ";
+        assert_eq!(totals(input), (4, 13));
+    }
+
+    #[test]
+    fn test_blockers() {
+        let input = "01234\n#x##5\nx9876";
+        let grid = parse(input.as_bytes(), b"#x");
+        let starts = grid.starts();
+
+        assert_eq!(grid.solve(&starts), 1);
+        let trails = grid.trails(&starts, Limits::default());
+        assert_eq!(grid.render(&trails[0].trails[0]), "01234\n####5\n#9876");
+
+        // `.` stays impassable alongside the extra blockers
+        let input = "01234\n#.##5\n.9876";
+        let grid = parse(input.as_bytes(), b"#");
+        assert_eq!(process(&grid), 1);
+        let trails = grid.trails(&grid.starts(), Limits::default());
+        assert_eq!(grid.render(&trails[0].trails[0]), "01234\n####5\n#9876");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid byte")]
+    fn test_unknown_blocker() {
+        parse("01#9".as_bytes(), b"");
+    }
+
+    #[test]
+    fn test_traffic() {
+        let input = EXAMPLE.trim().replace(' ', "");
+        let grid = parse(input.as_bytes(), b"");
+        let sweep = grid.sweep();
+        let traffic = grid.traffic();
+
+        // Every trail passes through one cell of each height
+        assert_eq!(traffic.iter().sum::<usize>(), 81 * 10);
+        for (offset, height) in grid.grid.iter().enumerate() {
+            match height {
+                0 => assert_eq!(traffic[offset], sweep.rating(offset)),
+                9 => assert!(traffic[offset] > 0),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let input = "0123\n7654\n89..";
+        let grid = parse(input.as_bytes(), b"");
+        let traffic = grid.traffic();
+        assert_eq!(traffic, [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0]);
+
+        let mut ppm = Vec::new();
+        write_ppm(&grid, &traffic, &mut ppm).unwrap();
+        let header = b"P6\n32 24\n255\n";
+        assert_eq!(ppm[..header.len()], *header);
+        assert_eq!(ppm.len(), header.len() + 32 * 24 * 3);
+        assert_eq!(ppm[header.len()..header.len() + 3], [255, 255, 255]);
+
+        let wall = header.len() + (2 * HEATMAP_SCALE * 32 + 2 * HEATMAP_SCALE) * 3;
+        assert_eq!(ppm[wall..wall + 3], WALL_COLOR);
+        assert_eq!(ppm[wall - 3..wall], [255, 255, 255]);
+
+        let term = render_heatmap(&grid, &traffic);
+        assert!(term.starts_with("\x1b[30;48;2;255;255;255m0"));
+        assert!(term.contains("\x1b[97;48;2;48;48;48m#"));
+        assert_eq!(term.lines().count(), 3);
+
+        assert_eq!(heat_color(0.0), [0, 0, 0]);
+        assert_eq!(heat_color(0.25), [0, 0, 160]);
+        assert_eq!(heat_color(2.0), [255, 255, 255]);
     }
 }
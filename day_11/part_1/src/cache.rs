@@ -0,0 +1,134 @@
+//! The memo engine's cache, optionally bounded, keeping count of how well it
+//! does.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    hash::Hash,
+};
+
+/// Which entry a full cache drops to make room.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eviction {
+    /// The entry read or written longest ago.
+    Lru,
+    /// The entry written longest ago.
+    Fifo,
+}
+
+impl TryFrom<&str> for Eviction {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "lru" => Ok(Eviction::Lru),
+            "fifo" => Ok(Eviction::Fifo),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub hits: usize,
+    pub misses: usize,
+    pub evictions: usize,
+    pub size: usize,
+    /// The most entries the cache has held at once.
+    pub peak: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lookups = self.hits + self.misses;
+        let rate = match lookups {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        };
+
+        writeln!(f, "Cache hits: {} ({:.1}%)", self.hits, rate * 100.0)?;
+        writeln!(f, "Cache misses: {}", self.misses)?;
+        writeln!(f, "Cache evictions: {}", self.evictions)?;
+        write!(f, "Cache size: {} (peak {})", self.size, self.peak)
+    }
+}
+
+pub struct Cache<K, V> {
+    /// Each value along with when it was last used, as `eviction` sees it.
+    entries: HashMap<K, (V, u64)>,
+    /// The keys by when they were last used. Only kept up when there is a
+    /// capacity to evict for.
+    order: BTreeMap<u64, K>,
+    clock: u64,
+    capacity: Option<usize>,
+    eviction: Eviction,
+    stats: Stats,
+}
+
+impl<K: Clone + Eq + Hash, V: Copy> Cache<K, V> {
+    /// A cache holding at most `capacity` entries, if there is one.
+    pub fn new(capacity: Option<usize>, eviction: Eviction) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            clock: 0,
+            capacity,
+            eviction,
+            stats: Stats::default(),
+        }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let Some((value, used)) = self.entries.get_mut(key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+
+        self.stats.hits += 1;
+
+        if self.capacity.is_some() && self.eviction == Eviction::Lru {
+            self.clock += 1;
+            let key = self.order.remove(used).expect("Cache entry out of order");
+            *used = self.clock;
+            self.order.insert(self.clock, key);
+        }
+
+        Some(*value)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        if let Some(capacity) = self.capacity {
+            if capacity == 0 {
+                return;
+            }
+
+            if let Some((_, used)) = self.entries.remove(&key) {
+                self.order.remove(&used);
+            } else if self.entries.len() == capacity {
+                let (_, oldest) = self.order.pop_first().expect("Full cache is empty");
+                self.entries.remove(&oldest);
+                self.stats.evictions += 1;
+            }
+
+            self.clock += 1;
+            self.order.insert(self.clock, key.clone());
+        }
+
+        self.entries.insert(key, (value, self.clock));
+        self.stats.peak = self.stats.peak.max(self.entries.len());
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            size: self.entries.len(),
+            ..self.stats
+        }
+    }
+}
+
+impl<K: Clone + Eq + Hash, V: Copy> Default for Cache<K, V> {
+    /// An unbounded cache.
+    fn default() -> Self {
+        Self::new(None, Eviction::Lru)
+    }
+}
@@ -0,0 +1,343 @@
+//! The closed set of stone values some stones can ever turn into, and the
+//! transitions between them.
+//!
+//! Every blink is a linear map on how many stones there are of each value,
+//! so blinking `n` times is the `n`th power of the transition matrix. Squaring
+//! it makes the number of blinks cost only its logarithm, at a cost cubic in
+//! the number of values. Real inputs reach a few thousand values, too many to
+//! square for. Modulo a prime, the count follows a linear recurrence no longer
+//! than the number of values instead, which blinking twice that many times
+//! pins down, and which gets to any number of blinks in logarithmic time too.
+
+use std::collections::HashMap;
+
+use crate::{
+    recurrence::{self, Recurrence},
+    rules::Rules,
+};
+
+/// The most values the transition matrix is squared for, as that is cubic
+/// in them.
+const MATRIX_LIMIT: usize = 1 << 10;
+
+/// The most transitions followed when blinking one blink at a time, past
+/// which counting gives up rather than run for minutes.
+const BLINK_LIMIT: u128 = 1 << 32;
+
+/// How [`Closure::count`] gets through the blinks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// Follows every transition, blink by blink.
+    Blink,
+    /// Raises the transition matrix to the number of blinks by squaring.
+    Square,
+    /// Finds the linear recurrence the count follows modulo a prime, and
+    /// jumps ahead along it.
+    Recurrence,
+}
+
+pub struct Closure {
+    /// Every reachable value, in the order they were found.
+    values: Vec<u64>,
+    /// Where each value is in `values`.
+    index: HashMap<u64, usize>,
+    /// For each value, the index of each stone it turns into. Values it
+    /// turns into more than once appear more than once.
+    transitions: Vec<Vec<usize>>,
+}
+
+impl Closure {
+    /// Finds every value `stones` can turn into, or `None` if there are more
+    /// than `limit`.
+    pub fn new(stones: &[u64], rules: &Rules, limit: usize) -> Option<Self> {
+        let mut index = HashMap::new();
+        let mut values = Vec::new();
+        let mut transitions = Vec::new();
+
+        for stone in stones {
+            index.entry(*stone).or_insert_with(|| {
+                values.push(*stone);
+                values.len() - 1
+            });
+        }
+
+        let mut i = 0;
+        while i < values.len() {
+            let mut next = Vec::new();
+            rules.apply(values[i], |stone| {
+                next.push(*index.entry(stone).or_insert_with(|| {
+                    values.push(stone);
+                    values.len() - 1
+                }));
+            });
+
+            if values.len() > limit {
+                return None;
+            }
+
+            transitions.push(next);
+            i += 1;
+        }
+
+        Some(Self {
+            values,
+            index,
+            transitions,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn transitions(&self) -> usize {
+        self.transitions.iter().map(Vec::len).sum()
+    }
+
+    /// Each value, in the order they were found, with the values of the
+    /// stones it turns into.
+    pub fn graph(&self) -> impl '_ + Iterator<Item = (u64, impl '_ + Iterator<Item = u64>)> {
+        self.values
+            .iter()
+            .zip(&self.transitions)
+            .map(|(value, next)| {
+                let next = next.iter().map(|i| self.values[*i]);
+                (*value, next)
+            })
+    }
+
+    pub fn largest(&self) -> u64 {
+        self.values.iter().copied().max().unwrap_or(0)
+    }
+
+    /// How many values a stone can turn back into after some blinks.
+    pub fn recurring(&self) -> usize {
+        (0..self.size())
+            .filter(|&start| {
+                let mut seen = vec![false; self.size()];
+                let mut stack = self.transitions[start].clone();
+
+                while let Some(i) = stack.pop() {
+                    if i == start {
+                        return true;
+                    }
+                    if !std::mem::replace(&mut seen[i], true) {
+                        stack.extend(&self.transitions[i]);
+                    }
+                }
+
+                false
+            })
+            .count()
+    }
+
+    /// How many stones `stones` turn into after `blinks` blinks, reduced
+    /// modulo `modulo` if there is one, by the cheapest method that can.
+    /// `stones` must be ones the closure was built from.
+    pub fn count(&self, stones: &[u64], blinks: usize, modulo: Option<u64>) -> u128 {
+        let method = self.method(blinks, modulo).unwrap_or_else(|| {
+            panic!(
+                "Too many blinks to follow through {} values without a prime modulo",
+                self.size()
+            )
+        });
+        self.count_by(stones, blinks, modulo, method)
+    }
+
+    /// The cheapest method that counts `blinks` blinks, going by how many
+    /// steps each takes, or `None` if blinking is the only one and it takes
+    /// too many.
+    pub fn method(&self, blinks: usize, modulo: Option<u64>) -> Option<Method> {
+        let size = self.size() as u128;
+        let transitions = self.transitions() as u128;
+        let bits = (usize::BITS - blinks.leading_zeros()) as u128;
+
+        let blink = Some(transitions * blinks as u128).filter(|steps| *steps <= BLINK_LIMIT);
+        let square = (self.size() <= MATRIX_LIMIT).then(|| size.pow(3) * bits);
+        let recurrence = modulo
+            .filter(|modulo| recurrence::is_prime(*modulo))
+            .map(|_| 2 * size * transitions + 2 * size.pow(2) * (bits + 1));
+
+        [
+            (blink, Method::Blink),
+            (square, Method::Square),
+            (recurrence, Method::Recurrence),
+        ]
+        .into_iter()
+        .filter_map(|(steps, method)| Some((steps?, method)))
+        .min_by_key(|(steps, _)| *steps)
+        .map(|(_, method)| method)
+    }
+
+    /// Like [`Closure::count`], but by `method` however long it takes.
+    /// [`Method::Recurrence`] needs a prime modulo.
+    pub fn count_by(
+        &self,
+        stones: &[u64],
+        blinks: usize,
+        modulo: Option<u64>,
+        method: Method,
+    ) -> u128 {
+        let arith = Arith(modulo);
+
+        let mut counts = vec![0; self.size()];
+        for stone in stones {
+            let i = *self.index.get(stone).expect("Stone outside the closure");
+            counts[i] = arith.add(counts[i], 1);
+        }
+
+        let total = |counts: &[u128]| {
+            counts
+                .iter()
+                .fold(0, |total, count| arith.add(total, *count))
+        };
+
+        match method {
+            Method::Blink => {
+                let counts = (0..blinks).fold(counts, |counts, _| self.blink(&counts, arith));
+                total(&counts)
+            }
+
+            Method::Square => total(&self.power(counts, blinks, arith)),
+
+            Method::Recurrence => {
+                let prime = modulo
+                    .filter(|modulo| recurrence::is_prime(*modulo))
+                    .expect("The recurrence needs a prime modulo");
+
+                // No recurrence is longer than the number of values, and
+                // twice its length of terms pins it down
+                let mut seq = Vec::with_capacity(2 * self.size());
+                for _ in 0..(2 * self.size()).min(blinks.saturating_add(1)) {
+                    seq.push(total(&counts) as u64);
+                    counts = self.blink(&counts, arith);
+                }
+
+                match seq.get(blinks) {
+                    Some(count) => *count as u128,
+                    None => Recurrence::find(&seq, prime).nth(blinks) as u128,
+                }
+            }
+        }
+    }
+
+    /// The counts of each value one blink after `counts`.
+    fn blink(&self, counts: &[u128], arith: Arith) -> Vec<u128> {
+        let mut next = vec![0; self.size()];
+
+        for (count, transitions) in counts.iter().zip(&self.transitions) {
+            if *count == 0 {
+                continue;
+            }
+            for to in transitions {
+                next[*to] = arith.add(next[*to], *count);
+            }
+        }
+
+        next
+    }
+
+    /// The counts of each value `blinks` blinks after `counts`, by raising the
+    /// transition matrix to that power.
+    fn power(&self, mut counts: Vec<u128>, blinks: usize, arith: Arith) -> Vec<u128> {
+        let mut power = Matrix::new(self.size());
+        for (from, next) in self.transitions.iter().enumerate() {
+            for to in next {
+                let cell = power.cell_mut(from, *to);
+                *cell = arith.add(*cell, 1);
+            }
+        }
+
+        let mut blinks = blinks;
+        while blinks > 0 {
+            if blinks % 2 == 1 {
+                counts = power.apply(&counts, arith);
+            }
+
+            blinks /= 2;
+            if blinks > 0 {
+                power = power.square(arith);
+            }
+        }
+
+        counts
+    }
+}
+
+/// Counting exactly, panicking past `u128`, or modulo a number.
+#[derive(Clone, Copy)]
+struct Arith(Option<u64>);
+
+impl Arith {
+    fn reduce(self, num: u128) -> u128 {
+        match self.0 {
+            Some(modulo) => num % modulo as u128,
+            None => num,
+        }
+    }
+
+    fn add(self, a: u128, b: u128) -> u128 {
+        let sum = a.checked_add(b).expect("Too many stones to count");
+        self.reduce(sum)
+    }
+
+    /// Both factors are reduced already, so with a modulus they are under
+    /// `u64::MAX` and their product fits.
+    fn mul(self, a: u128, b: u128) -> u128 {
+        let product = a.checked_mul(b).expect("Too many stones to count");
+        self.reduce(product)
+    }
+}
+
+/// A square matrix, row by row. Row `from`, column `to` holds how many
+/// stones of value `from` turn into ones of value `to`.
+struct Matrix {
+    size: usize,
+    cells: Vec<u128>,
+}
+
+impl Matrix {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            cells: vec![0; size * size],
+        }
+    }
+
+    fn row(&self, from: usize) -> &[u128] {
+        &self.cells[from * self.size..(from + 1) * self.size]
+    }
+
+    fn cell_mut(&mut self, from: usize, to: usize) -> &mut u128 {
+        &mut self.cells[from * self.size + to]
+    }
+
+    /// The counts of each value after the blinks this matrix stands for.
+    fn apply(&self, counts: &[u128], arith: Arith) -> Vec<u128> {
+        let mut next = vec![0; self.size];
+
+        for (from, count) in counts.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            for (to, cell) in self.row(from).iter().enumerate() {
+                if *cell != 0 {
+                    next[to] = arith.add(next[to], arith.mul(*count, *cell));
+                }
+            }
+        }
+
+        next
+    }
+
+    fn square(&self, arith: Arith) -> Self {
+        let mut square = Self::new(self.size);
+
+        for from in 0..self.size {
+            let row = self.apply(self.row(from), arith);
+            square.cells[from * self.size..(from + 1) * self.size].copy_from_slice(&row);
+        }
+
+        square
+    }
+}
@@ -0,0 +1,200 @@
+//! Linear recurrences modulo a prime: finding the shortest one a sequence
+//! follows, and jumping far ahead along it.
+//!
+//! A sequence following a recurrence of length `L` is set by its first `L`
+//! terms, and its `n`th term is the remainder of `x^n` modulo the
+//! recurrence's characteristic polynomial, read with those terms in place of
+//! the powers of `x`. Raising `x` by squaring costs `O(L² log n)`.
+
+pub struct Recurrence {
+    prime: u64,
+    /// The first terms of the sequence, as many as there are coefficients.
+    init: Vec<u64>,
+    /// `coefficients[i]` multiplies the term `i + 1` places back.
+    coefficients: Vec<u64>,
+}
+
+impl Recurrence {
+    /// The shortest recurrence `seq` follows modulo `prime`, by
+    /// Berlekamp–Massey. The whole sequence only follows it too if `seq` is
+    /// at least twice as long as the recurrence.
+    pub fn find(seq: &[u64], prime: u64) -> Self {
+        let field = Field(prime);
+
+        // `current` and `previous` are connection polynomials, with the
+        // constant term first, and `len` is how many terms back `current`
+        // reaches.
+        let mut current = vec![1];
+        let mut previous = vec![1];
+        let mut len = 0;
+        let mut shift = 1;
+        let mut last = 1;
+
+        for (n, term) in seq.iter().enumerate() {
+            let discrepancy = (1..=len).fold(*term, |sum, i| {
+                field.add(sum, field.mul(current[i], seq[n - i]))
+            });
+
+            if discrepancy == 0 {
+                shift += 1;
+                continue;
+            }
+
+            let scale = field.mul(discrepancy, field.inv(last));
+            let before = current.clone();
+            current.resize(current.len().max(previous.len() + shift), 0);
+            for (i, coefficient) in previous.iter().enumerate() {
+                let delta = field.mul(scale, *coefficient);
+                current[i + shift] = field.sub(current[i + shift], delta);
+            }
+
+            if 2 * len <= n {
+                len = n + 1 - len;
+                previous = before;
+                last = discrepancy;
+                shift = 1;
+            } else {
+                shift += 1;
+            }
+        }
+
+        current.resize(len + 1, 0);
+        Self {
+            prime,
+            init: seq[..len].to_vec(),
+            coefficients: current[1..].iter().map(|c| field.sub(0, *c)).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    /// The `n`th term of the sequence, counting from zero.
+    pub fn nth(&self, n: usize) -> u64 {
+        if n < self.len() {
+            return self.init[n];
+        }
+
+        let field = Field(self.prime);
+
+        // `x^n` modulo the characteristic polynomial, lowest power first,
+        // going through the bits of `n` from the top
+        let mut power = vec![1];
+        for bit in (0..usize::BITS - n.leading_zeros()).rev() {
+            power = self.reduce(self.square(&power));
+            if n >> bit & 1 == 1 {
+                power.insert(0, 0);
+                power = self.reduce(power);
+            }
+        }
+
+        power
+            .iter()
+            .zip(&self.init)
+            .fold(0, |sum, (c, term)| field.add(sum, field.mul(*c, *term)))
+    }
+
+    fn square(&self, poly: &[u64]) -> Vec<u64> {
+        let field = Field(self.prime);
+        let mut square = vec![0; (2 * poly.len()).saturating_sub(1)];
+
+        for (i, a) in poly.iter().enumerate() {
+            if *a == 0 {
+                continue;
+            }
+            for (j, b) in poly.iter().enumerate() {
+                square[i + j] = field.add(square[i + j], field.mul(*a, *b));
+            }
+        }
+
+        square
+    }
+
+    /// Brings `poly` under the degree of the characteristic polynomial,
+    /// replacing each power from the top by the lower ones it equals.
+    fn reduce(&self, mut poly: Vec<u64>) -> Vec<u64> {
+        let field = Field(self.prime);
+        let len = self.len();
+
+        for top in (len..poly.len()).rev() {
+            let c = std::mem::take(&mut poly[top]);
+            if c == 0 {
+                continue;
+            }
+            for (back, coefficient) in self.coefficients.iter().enumerate() {
+                let i = top - back - 1;
+                poly[i] = field.add(poly[i], field.mul(c, *coefficient));
+            }
+        }
+
+        poly.truncate(len);
+        poly
+    }
+}
+
+/// Arithmetic modulo a prime.
+#[derive(Clone, Copy)]
+struct Field(u64);
+
+impl Field {
+    fn add(self, a: u64, b: u64) -> u64 {
+        match a.overflowing_add(b) {
+            (sum, false) if sum < self.0 => sum,
+            (sum, _) => sum.wrapping_sub(self.0),
+        }
+    }
+
+    fn sub(self, a: u64, b: u64) -> u64 {
+        self.add(a, self.0 - b)
+    }
+
+    fn mul(self, a: u64, b: u64) -> u64 {
+        (a as u128 * b as u128 % self.0 as u128) as u64
+    }
+
+    fn pow(self, mut base: u64, mut exp: u64) -> u64 {
+        let mut power = 1 % self.0;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                power = self.mul(power, base);
+            }
+            base = self.mul(base, base);
+            exp >>= 1;
+        }
+        power
+    }
+
+    /// The inverse of a nonzero `a`, by Fermat's little theorem.
+    fn inv(self, a: u64) -> u64 {
+        self.pow(a, self.0 - 2)
+    }
+}
+
+/// Whether `n` is prime, by Miller–Rabin with bases that make it exact for
+/// every `u64`.
+pub fn is_prime(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+    if n < 2 {
+        return false;
+    }
+    if let Some(base) = BASES.iter().find(|base| n.is_multiple_of(**base)) {
+        return n == *base;
+    }
+
+    let field = Field(n);
+    let twos = (n - 1).trailing_zeros();
+    let odd = (n - 1) >> twos;
+
+    BASES.iter().all(|base| {
+        let mut x = field.pow(*base, odd);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        (1..twos).any(|_| {
+            x = field.mul(x, x);
+            x == n - 1
+        })
+    })
+}
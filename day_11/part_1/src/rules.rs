@@ -0,0 +1,180 @@
+//! What a stone turns into when you blink, as an ordered list of rules.
+//!
+//! Rules are written `predicate -> transform` and separated by `;`. The first
+//! rule whose predicate matches a stone, and whose transform can be applied to
+//! it, decides what it turns into. A stone no rule applies to stays as it is.
+//! The puzzle's rules are `0 -> 1; even -> split 2; any -> mul 2024`.
+//!
+//! Predicates:
+//! - `N`: the stone is engraved with `N`.
+//! - `even`: the stone has an even number of digits.
+//! - `digits%K`: the stone's number of digits is a multiple of `K`.
+//! - `any`: every stone.
+//!
+//! Transforms:
+//! - `N`: the stone is replaced by one engraved with `N`.
+//! - `split K`: the digits are split into `K` stones of equal width, dropping
+//!   leading zeroes. Only applies to stones whose number of digits is a
+//!   multiple of `K`.
+//! - `mul K`: the number is multiplied by `K`.
+//! - `add K`: `K` is added to the number.
+
+use std::str::FromStr;
+
+use crate::Digits;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Predicate {
+    Equals(u64),
+    DigitsMultipleOf(u32),
+    Any,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    Replace(u64),
+    Split(u32),
+    Multiply(u64),
+    Add(u64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule {
+    pub predicate: Predicate,
+    pub transform: Transform,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rules(Vec<Rule>);
+
+impl Predicate {
+    fn matches(self, stone: u64) -> bool {
+        match self {
+            Predicate::Equals(num) => stone == num,
+            Predicate::DigitsMultipleOf(k) => stone.digits().is_multiple_of(k),
+            Predicate::Any => true,
+        }
+    }
+}
+
+impl Transform {
+    fn applies(self, stone: u64) -> bool {
+        match self {
+            Transform::Split(parts) => stone.digits().is_multiple_of(parts),
+            _ => true,
+        }
+    }
+
+    fn apply(self, stone: u64, mut emit: impl FnMut(u64)) {
+        match self {
+            Transform::Replace(num) => emit(num),
+            Transform::Split(parts) => stone
+                .split(parts)
+                .expect("Cannot split stone")
+                .for_each(emit),
+            Transform::Multiply(k) => emit(stone.checked_mul(k).expect("Stone too large")),
+            Transform::Add(k) => emit(stone.checked_add(k).expect("Stone too large")),
+        }
+    }
+}
+
+impl Rules {
+    /// Calls `emit` with each stone `stone` turns into, in order.
+    pub fn apply(&self, stone: u64, mut emit: impl FnMut(u64)) {
+        let rule = self
+            .0
+            .iter()
+            .find(|rule| rule.predicate.matches(stone) && rule.transform.applies(stone));
+
+        match rule {
+            Some(rule) => rule.transform.apply(stone, emit),
+            None => emit(stone),
+        }
+    }
+}
+
+impl Default for Rules {
+    /// The puzzle's rules.
+    fn default() -> Self {
+        Self(vec![
+            Rule {
+                predicate: Predicate::Equals(0),
+                transform: Transform::Replace(1),
+            },
+            Rule {
+                predicate: Predicate::DigitsMultipleOf(2),
+                transform: Transform::Split(2),
+            },
+            Rule {
+                predicate: Predicate::Any,
+                transform: Transform::Multiply(2024),
+            },
+        ])
+    }
+}
+
+impl FromStr for Predicate {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "even" => Ok(Predicate::DigitsMultipleOf(2)),
+            "any" => Ok(Predicate::Any),
+            _ => match s.strip_prefix("digits%") {
+                Some(k) => match k.parse() {
+                    Ok(0) | Err(_) => Err("Invalid digit count"),
+                    Ok(k) => Ok(Predicate::DigitsMultipleOf(k)),
+                },
+                None => s
+                    .parse()
+                    .map(Predicate::Equals)
+                    .map_err(|_| "Invalid predicate"),
+            },
+        }
+    }
+}
+
+impl FromStr for Transform {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |num: &str| num.trim().parse().map_err(|_| "Invalid transform");
+
+        match s.split_once(' ') {
+            Some(("split", parts)) => match parts.trim().parse() {
+                Ok(0) | Err(_) => Err("Invalid part count"),
+                Ok(parts) => Ok(Transform::Split(parts)),
+            },
+            Some(("mul", k)) => parse(k).map(Transform::Multiply),
+            Some(("add", k)) => parse(k).map(Transform::Add),
+            Some(_) => Err("Unknown transform"),
+            None => parse(s).map(Transform::Replace),
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (predicate, transform) = s.split_once("->").ok_or("Missing ->")?;
+
+        Ok(Rule {
+            predicate: predicate.trim().parse()?,
+            transform: transform.trim().parse()?,
+        })
+    }
+}
+
+impl FromStr for Rules {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(';')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
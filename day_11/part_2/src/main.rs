@@ -1,38 +1,216 @@
 use std::{
     collections::HashMap,
     fs::File,
-    io::{BufReader, Bytes, Read},
+    io::{BufRead, BufReader, Bytes},
 };
 
+use cache::{Cache, Eviction};
+use closure::Closure;
+use rules::Rules;
+
+mod cache;
+mod closure;
+mod recurrence;
+mod rules;
+
+/// The most values `--closure` and the matrix engine look through before
+/// giving up, for rules under which stones grow forever.
+const CLOSURE_LIMIT: usize = 1 << 20;
+
 fn main() {
-    let blinks = std::env::args()
-        .nth(1)
-        .expect("No blinks provided")
-        .parse()
-        .expect("Cannot parse blinks");
-    let path = std::env::args()
-        .nth(2)
-        .expect("No input file path provided");
+    let mut blinks = None;
+    let mut path = None;
+    let mut engine = Engine::Memo;
+    let mut distribution = false;
+    let mut rules = Rules::default();
+    let mut closure = false;
+    let mut modulo = None;
+    let mut capacity = None;
+    let mut eviction = Eviction::Lru;
+    let mut verbose = false;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--engine" => {
+                let name = args.next().expect("No engine provided");
+                engine = Engine::try_from(name.as_str()).expect("Unknown engine");
+            }
+            "--distribution" => distribution = true,
+            "--rules" => {
+                let spec = args.next().expect("No rules provided");
+                rules = spec.parse().expect("Cannot parse rules");
+            }
+            "--closure" => closure = true,
+            "--modulo" => {
+                let num = args.next().expect("No modulo provided");
+                let num = num.parse().expect("Cannot parse modulo");
+                assert!(num > 0, "Modulo must be positive");
+                modulo = Some(num);
+            }
+            "--cache-capacity" => {
+                let num = args.next().expect("No cache capacity provided");
+                capacity = Some(num.parse().expect("Cannot parse cache capacity"));
+            }
+            "--eviction" => {
+                let name = args.next().expect("No eviction policy provided");
+                eviction = Eviction::try_from(name.as_str()).expect("Unknown eviction policy");
+            }
+            "--verbose" => verbose = true,
+            _ if blinks.is_none() => blinks = Some(arg.parse().expect("Cannot parse blinks")),
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("Unexpected argument: {arg}"),
+        }
+    }
+
+    let blinks = blinks.expect("No blinks provided");
+    let path = path.expect("No input file path provided");
 
     let file = File::open(path).expect("Cannot open file");
     let reader = BufReader::new(file);
 
-    let count = process(reader, blinks);
+    if distribution {
+        let stones = NumCursor::new(reader).collect::<Vec<_>>();
+
+        for (blink, histogram) in Histogram::new(&stones)
+            .evolve(&rules)
+            .take(blinks + 1)
+            .enumerate()
+        {
+            println!(
+                "Blink {blink}: {} stones, {} distinct",
+                histogram.total(),
+                histogram.distinct()
+            );
+
+            let mut counts = histogram.counts().iter().collect::<Vec<_>>();
+            counts.sort_unstable();
+            for (stone, count) in counts {
+                println!("  {stone}: {count}");
+            }
+        }
+
+        return;
+    }
+
+    let stones = NumCursor::new(reader).collect::<Vec<_>>();
+
+    if closure {
+        let closure = Closure::new(&stones, &rules, CLOSURE_LIMIT).expect("Closure too large");
+        println!("Values: {}", closure.size());
+        println!("Transitions: {}", closure.transitions());
+        println!("Recurring: {}", closure.recurring());
+        println!("Largest: {}", closure.largest());
+
+        println!("Graph:");
+        for (value, next) in closure.graph() {
+            let next = next.map(|stone| stone.to_string()).collect::<Vec<_>>();
+            println!("  {value} -> {}", next.join(" "));
+        }
+    }
+
+    let mut cache = Cache::new(capacity, eviction);
+    let count = process(&stones, blinks, engine, &rules, modulo, &mut cache);
 
     println!("Count: {count}");
+
+    if verbose && engine == Engine::Memo {
+        println!("{}", cache.stats());
+    }
+}
+
+/// How the stones are counted after a number of blinks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Engine {
+    /// Keeps every stone in order in a `Vec`, as the puzzle describes.
+    Vec,
+    /// Counts the stones each stone turns into recursively, memoised by
+    /// stone and blinks remaining.
+    Memo,
+    /// Keeps how many stones there are of each value, blink by blink.
+    Histogram,
+    /// Raises the transition matrix between every value the stones can turn
+    /// into to the power of the number of blinks. Past a thousand or so
+    /// values, only counting modulo a prime gets through astronomical numbers
+    /// of blinks, along the linear recurrence the count follows.
+    Matrix,
 }
 
-fn process(reader: impl Read, blinks: usize) -> usize {
-    let mut count = 0;
-    let mut cache = HashMap::new();
-    for num in NumCursor::new(reader) {
-        count += apply(num, blinks, &mut cache);
+impl TryFrom<&str> for Engine {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "vec" => Ok(Engine::Vec),
+            "memo" => Ok(Engine::Memo),
+            "histogram" => Ok(Engine::Histogram),
+            "matrix" => Ok(Engine::Matrix),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Counts are `u128`, as the number of stones roughly doubles every couple of
+/// blinks and outgrows `u64` after a few hundred. Past that, counting modulo
+/// a number with the histogram or matrix engine keeps going.
+fn process(
+    stones: &[u64],
+    blinks: usize,
+    engine: Engine,
+    rules: &Rules,
+    modulo: Option<u64>,
+    cache: &mut Cache<(u64, usize), u128>,
+) -> u128 {
+    let count = match engine {
+        Engine::Vec => {
+            let mut nums = stones.to_vec();
+            for _ in 0..blinks {
+                blink(&mut nums, rules);
+            }
+            nums.len() as u128
+        }
+
+        Engine::Memo => stones
+            .iter()
+            .map(|num| count(*num, blinks, rules, cache))
+            .sum(),
+
+        Engine::Histogram => {
+            let mut histogram = Histogram::new(stones);
+            for _ in 0..blinks {
+                histogram = histogram.blink(rules);
+                if let Some(modulo) = modulo {
+                    histogram.reduce(modulo);
+                }
+            }
+            histogram.total()
+        }
+
+        Engine::Matrix => {
+            let closure = Closure::new(stones, rules, CLOSURE_LIMIT);
+            let closure = closure.expect("Closure too large for the matrix engine");
+            closure.count(stones, blinks, modulo)
+        }
+    };
+
+    match modulo {
+        Some(modulo) => count % modulo as u128,
+        None => count,
     }
+}
 
-    count
+/// Replaces every stone with what it turns into after one blink, keeping
+/// them in order.
+fn blink(nums: &mut Vec<u64>, rules: &Rules) {
+    let mut next = Vec::with_capacity(nums.len());
+    for num in nums.iter() {
+        rules.apply(*num, |stone| next.push(stone));
+    }
+    *nums = next;
 }
 
-fn apply(num: usize, mut rem: usize, cache: &mut HashMap<(usize, usize), usize>) -> usize {
+/// How many stones `num` turns into after `rem` blinks.
+fn count(num: u64, mut rem: usize, rules: &Rules, cache: &mut Cache<(u64, usize), u128>) -> u128 {
     if rem == 0 {
         return 1;
     }
@@ -40,38 +218,113 @@ fn apply(num: usize, mut rem: usize, cache: &mut HashMap<(usize, usize), usize>)
     rem -= 1;
 
     if let Some(count) = cache.get(&(num, rem)) {
-        return *count;
+        return count;
     }
 
-    if num == 0 {
-        let result = apply(1, rem, cache);
-        cache.insert((num, rem), result);
-        return result;
+    let mut result = 0u128;
+    rules.apply(num, |stone| {
+        result = result
+            .checked_add(count(stone, rem, rules, cache))
+            .expect("Too many stones to count");
+    });
+
+    cache.insert((num, rem), result);
+
+    result
+}
+
+/// How many stones there are of each value. Order doesn't affect what any
+/// stone turns into, so this is all a blink needs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Histogram(HashMap<u64, u128>);
+
+impl Histogram {
+    fn new(stones: &[u64]) -> Self {
+        let mut histogram = Self::default();
+        for stone in stones {
+            histogram.add(*stone, 1);
+        }
+        histogram
     }
 
-    let string = num.to_string();
-    if string.len() % 2 == 1 {
-        let result = apply(num * 2024, rem, cache);
-        cache.insert((num, rem), result);
-        return result;
+    fn add(&mut self, stone: u64, count: u128) {
+        let total = self.0.entry(stone).or_default();
+        *total = total.checked_add(count).expect("Too many stones to count");
     }
 
-    let (first_half, second_half) = string.split_at(string.len() / 2);
-    let first_half = first_half.parse().expect("Cannot parse number");
-    let second_half = second_half.parse().expect("Cannot parse number");
+    fn blink(&self, rules: &Rules) -> Self {
+        let mut next = Self::default();
 
-    let first_half = apply(first_half, rem, cache);
+        for (&stone, &count) in &self.0 {
+            rules.apply(stone, |stone| next.add(stone, count));
+        }
 
-    let result = first_half + apply(second_half, rem, cache);
+        next
+    }
 
-    cache.insert((num, rem), result);
+    /// Reduces every count modulo `modulo`, which keeps the total in range.
+    fn reduce(&mut self, modulo: u64) {
+        for count in self.0.values_mut() {
+            *count %= modulo as u128;
+        }
+    }
 
-    result
+    /// This histogram, followed by the one after each further blink.
+    fn evolve(self, rules: &Rules) -> impl '_ + Iterator<Item = Self> {
+        std::iter::successors(Some(self), |histogram| Some(histogram.blink(rules)))
+    }
+
+    fn counts(&self) -> &HashMap<u64, u128> {
+        &self.0
+    }
+
+    fn total(&self) -> u128 {
+        self.0
+            .values()
+            .try_fold(0u128, |total, count| total.checked_add(*count))
+            .expect("Too many stones to count")
+    }
+
+    fn distinct(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Splitting a number into equal runs of its decimal digits.
+trait Digits: Sized {
+    /// How many decimal digits the number has, counting zero as one digit.
+    fn digits(self) -> u32;
+
+    /// The number's digits split into `parts` numbers of equal width, first
+    /// to last, if its number of digits is a multiple of `parts`. Leading
+    /// zeroes of each part are dropped.
+    fn split(self, parts: u32) -> Option<impl Iterator<Item = Self>>;
+}
+
+impl Digits for u64 {
+    fn digits(self) -> u32 {
+        self.checked_ilog10().unwrap_or(0) + 1
+    }
+
+    fn split(self, parts: u32) -> Option<impl Iterator<Item = Self>> {
+        let digits = self.digits();
+        if parts == 0 || !digits.is_multiple_of(parts) {
+            return None;
+        }
+
+        // Only saturates for a single part the width of the type, and the
+        // first part is never taken modulo `pow`.
+        let pow = 10u64.saturating_pow(digits / parts);
+        Some((0..parts).rev().map(move |i| match self / pow.pow(i) {
+            part if i + 1 == parts => part,
+            part => part % pow,
+        }))
+    }
 }
 
 struct NumCursor<R>(Bytes<R>, String);
 
-impl<R: Read> NumCursor<R> {
+impl<R: BufRead> NumCursor<R> {
     fn new(reader: R) -> Self {
         Self(reader.bytes(), String::new())
     }
@@ -80,17 +333,17 @@ impl<R: Read> NumCursor<R> {
         self.0.next().map(|byte| byte.expect("Cannot read byte"))
     }
 
-    fn parse_clear(&mut self) -> usize {
+    fn parse_clear(&mut self) -> u64 {
         let result = self.1.parse().expect("Cannot parse number");
         self.1.clear();
         result
     }
 
-    fn next(&mut self) -> Option<usize> {
+    fn next(&mut self) -> Option<u64> {
         loop {
             let byte = match self.next_byte() {
                 Some(byte) => byte,
-                None if self.1.len() > 0 => return Some(self.parse_clear()),
+                None if !self.1.is_empty() => return Some(self.parse_clear()),
                 None => return None,
             };
 
@@ -103,8 +356,8 @@ impl<R: Read> NumCursor<R> {
     }
 }
 
-impl<R: Read> Iterator for NumCursor<R> {
-    type Item = usize;
+impl<R: BufRead> Iterator for NumCursor<R> {
+    type Item = u64;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.next()
@@ -114,6 +367,18 @@ impl<R: Read> Iterator for NumCursor<R> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        closure::Method,
+        recurrence::{is_prime, Recurrence},
+    };
+
+    /// The engines that work on any input. The matrix engine only takes
+    /// stones that turn into few enough values, so it's tested on its own.
+    const ENGINES: [Engine; 3] = [Engine::Vec, Engine::Memo, Engine::Histogram];
+
+    fn stones(input: &str) -> Vec<u64> {
+        NumCursor::new(input.as_bytes()).collect()
+    }
 
     #[test]
     fn test_cursor() {
@@ -131,14 +396,489 @@ mod tests {
 
     #[test]
     fn test() {
-        let test = r"0 1 10 99 999";
+        for engine in ENGINES {
+            let test = r"0 1 10 99 999";
+
+            let rules = Rules::default();
+
+            let result = process(
+                &stones(test),
+                1,
+                engine,
+                &rules,
+                None,
+                &mut Cache::default(),
+            );
+            assert_eq!(result, 7);
+
+            let test = r"125 17";
+
+            let result = process(
+                &stones(test),
+                6,
+                engine,
+                &rules,
+                None,
+                &mut Cache::default(),
+            );
+            assert_eq!(result, 22);
+
+            let result = process(
+                &stones(test),
+                25,
+                engine,
+                &rules,
+                None,
+                &mut Cache::default(),
+            );
+            assert_eq!(result, 55312);
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        let rules = Rules::default();
+        let mut histograms = Histogram::new(&[125, 17]).evolve(&rules);
+
+        let expected: [&[u64]; 7] = [
+            &[125, 17],
+            &[253000, 1, 7],
+            &[253, 0, 2024, 14168],
+            &[512072, 1, 20, 24, 28676032],
+            &[512, 72, 2024, 2, 0, 2, 4, 2867, 6032],
+            &[1036288, 7, 2, 20, 24, 4048, 1, 4048, 8096, 28, 67, 60, 32],
+            &[
+                2097446912, 14168, 4048, 2, 0, 2, 4, 40, 48, 2024, 40, 48, 80, 96, 2, 8, 6, 7, 6,
+                0, 3, 2,
+            ],
+        ];
+
+        for stones in expected {
+            let histogram = histograms.next().unwrap();
+            assert_eq!(histogram, Histogram::new(stones));
+            assert_eq!(histogram.total(), stones.len() as u128);
+        }
+
+        let histogram = histograms.next().unwrap();
+        assert_eq!(histogram.counts().get(&4048), Some(&4));
+        assert_eq!(histogram.counts().get(&0), Some(&3));
+        assert_eq!((histogram.total(), histogram.distinct()), (31, 19));
+    }
+
+    #[test]
+    fn test_engines() {
+        let test = r"0 7 2024 999999 4048 1";
+        let rules = Rules::default();
+
+        for blinks in 0..20 {
+            let counts = ENGINES.map(|engine| {
+                process(
+                    &stones(test),
+                    blinks,
+                    engine,
+                    &rules,
+                    None,
+                    &mut Cache::default(),
+                )
+            });
+            assert!(counts.iter().all(|count| *count == counts[0]), "{blinks}");
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let puzzle = "0 -> 1; even -> split 2; any -> mul 2024".parse::<Rules>();
+        assert_eq!(puzzle, Ok(Rules::default()));
+
+        let rules = "0 -> 1; digits%3 -> split 3; any -> mul 3"
+            .parse::<Rules>()
+            .unwrap();
+
+        let mut nums = vec![123456, 7];
+        let expected: [&[u64]; 3] = [
+            &[12, 34, 56, 21],
+            &[36, 102, 168, 63],
+            &[108, 1, 0, 2, 1, 6, 8, 189],
+        ];
+        for stones in expected {
+            blink(&mut nums, &rules);
+            assert_eq!(nums, stones);
+        }
+
+        let test = r"0 7 2024 999999 4048 1";
+        for blinks in 0..15 {
+            let counts = ENGINES.map(|engine| {
+                process(
+                    &stones(test),
+                    blinks,
+                    engine,
+                    &rules,
+                    None,
+                    &mut Cache::default(),
+                )
+            });
+            assert!(counts.iter().all(|count| *count == counts[0]), "{blinks}");
+        }
+
+        // Stones no rule matches stay as they are.
+        let mut nums = vec![0, 5, 10];
+        blink(&mut nums, &"5 -> add 1".parse().unwrap());
+        assert_eq!(nums, [0, 6, 10]);
+
+        let mut nums = vec![0, 5, 10];
+        blink(&mut nums, &"".parse().unwrap());
+        assert_eq!(nums, [0, 5, 10]);
+
+        // A split that doesn't divide a stone's digits falls through to the
+        // next rule, or leaves the stone as it is.
+        let mut nums = vec![123, 1234, 0];
+        blink(&mut nums, &"any -> split 2; any -> mul 2".parse().unwrap());
+        assert_eq!(nums, [246, 12, 34, 0]);
+
+        let mut nums = vec![123, 1234];
+        blink(&mut nums, &"any -> split 2".parse().unwrap());
+        assert_eq!(nums, [123, 12, 34]);
+
+        let rules = "any -> split 2".parse().unwrap();
+        let counts = ENGINES
+            .map(|engine| process(&[123, 1234], 3, engine, &rules, None, &mut Cache::default()));
+        assert_eq!(counts, [5; 3]);
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        assert_eq!("0 -> 1 -> 2".parse::<Rules>(), Err("Unknown transform"));
+        assert_eq!("0 1".parse::<Rules>(), Err("Missing ->"));
+        assert_eq!("0 -> mul x".parse::<Rules>(), Err("Invalid transform"));
+        assert_eq!("odd -> 1".parse::<Rules>(), Err("Invalid predicate"));
+        assert_eq!("digits%0 -> 1".parse::<Rules>(), Err("Invalid digit count"));
+        assert_eq!("any -> split 0".parse::<Rules>(), Err("Invalid part count"));
+        assert_eq!("any -> div 2".parse::<Rules>(), Err("Unknown transform"));
+    }
+
+    /// The digit splitting done by formatting and parsing, to check the
+    /// arithmetic against.
+    fn split_string(num: u64, parts: usize) -> Option<Vec<u64>> {
+        let string = num.to_string();
+        if !string.len().is_multiple_of(parts) {
+            return None;
+        }
+
+        let parse = |part: &[u8]| {
+            let part = std::str::from_utf8(part).expect("Cannot read digits");
+            part.parse().expect("Cannot parse number")
+        };
+        Some(
+            string
+                .as_bytes()
+                .chunks(string.len() / parts)
+                .map(parse)
+                .collect(),
+        )
+    }
+
+    fn split(num: u64, parts: u32) -> Option<Vec<u64>> {
+        num.split(parts).map(Iterator::collect)
+    }
+
+    #[test]
+    fn test_digits() {
+        assert_eq!(0u64.digits(), 1);
+        assert_eq!(9u64.digits(), 1);
+        assert_eq!(10u64.digits(), 2);
+        assert_eq!(u64::MAX.digits(), 20);
+
+        assert_eq!(split(1000u64, 2), Some(vec![10, 0]));
+        assert_eq!(split(253000u64, 2), Some(vec![253, 0]));
+        assert_eq!(split(2024u64, 2), Some(vec![20, 24]));
+        assert_eq!(split(0u64, 2), None);
+        assert_eq!(split(999u64, 2), None);
+
+        assert_eq!(split(999u64, 3), Some(vec![9, 9, 9]));
+        assert_eq!(split(100200u64, 3), Some(vec![10, 2, 0]));
+        assert_eq!(split(2024u64, 1), Some(vec![2024]));
+        assert_eq!(split(2024u64, 0), None);
+    }
+
+    #[test]
+    fn test_split_properties() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let u64s = (0..20).flat_map(|exp| {
+            let pow = 10u64.pow(exp);
+            [pow - 1, pow, pow + 1]
+        });
+
+        for num in (0..10_000).map(|_| next() >> (next() % 64)).chain(u64s) {
+            for parts in 1..=4 {
+                assert_eq!(
+                    split(num, parts),
+                    split_string(num, parts as usize),
+                    "{num}"
+                );
+            }
+            assert_eq!(num.digits() as usize, num.to_string().len(), "{num}");
+        }
+    }
+
+    #[test]
+    fn test_wide_counts() {
+        let test = r"125 17";
+
+        let rules = Rules::default();
+
+        let memo = process(
+            &stones(test),
+            150,
+            Engine::Memo,
+            &rules,
+            None,
+            &mut Cache::default(),
+        );
+        let histogram = process(
+            &stones(test),
+            150,
+            Engine::Histogram,
+            &rules,
+            None,
+            &mut Cache::default(),
+        );
+        assert_eq!(memo, histogram);
+        assert!(memo > u64::MAX as u128);
+    }
+
+    #[test]
+    fn test_closure() {
+        let rules = Rules::default();
+
+        let closure = Closure::new(&[0], &rules, CLOSURE_LIMIT).unwrap();
+        assert_eq!(closure.size(), 54);
+        assert_eq!(closure.largest(), 36869184);
+        assert_eq!(closure.recurring(), 54);
+
+        let graph = closure
+            .graph()
+            .map(|(value, next)| (value, next.collect::<Vec<_>>()))
+            .take(4)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            graph,
+            [
+                (0, vec![1]),
+                (1, vec![2024]),
+                (2024, vec![20, 24]),
+                (20, vec![2, 0])
+            ]
+        );
+
+        let closure = Closure::new(&[125, 17], &rules, CLOSURE_LIMIT).unwrap();
+        assert_eq!(closure.size(), 76);
+        assert_eq!(closure.count(&[125, 17], 25, None), 55312);
+        assert_eq!(closure.count(&[125, 17], 25, Some(1000)), 312);
+
+        // Every method agrees, modulo small primes too.
+        for modulo in [2, 7, 1_000_000_007] {
+            for blinks in [0, 25, 151, 1000, 100_000] {
+                let counts = [Method::Blink, Method::Square, Method::Recurrence]
+                    .map(|method| closure.count_by(&[125, 17], blinks, Some(modulo), method));
+                assert!(counts.iter().all(|count| *count == counts[0]), "{blinks}");
+            }
+        }
 
-        let result = process(test.as_bytes(), 1);
-        assert_eq!(result, 7);
+        for blinks in 0..40 {
+            let matrix = process(
+                &[125, 17],
+                blinks,
+                Engine::Matrix,
+                &rules,
+                None,
+                &mut Cache::default(),
+            );
+            let memo = process(
+                &[125, 17],
+                blinks,
+                Engine::Memo,
+                &rules,
+                None,
+                &mut Cache::default(),
+            );
+            assert_eq!(matrix, memo, "{blinks}");
+        }
+
+        // Growing forever never closes.
+        let rules = "any -> add 1".parse().unwrap();
+        assert!(Closure::new(&[0], &rules, 100).is_none());
+    }
 
+    #[test]
+    fn test_modulo() {
         let test = r"125 17";
+        let rules = Rules::default();
+
+        let exact = process(
+            &stones(test),
+            150,
+            Engine::Histogram,
+            &rules,
+            None,
+            &mut Cache::default(),
+        );
+        for modulo in [1, 7, 1_000_000_007, u64::MAX] {
+            for engine in [Engine::Histogram, Engine::Matrix] {
+                let count = process(
+                    &stones(test),
+                    150,
+                    engine,
+                    &rules,
+                    Some(modulo),
+                    &mut Cache::default(),
+                );
+                assert_eq!(count, exact % modulo as u128);
+            }
+        }
+
+        let count = process(
+            &stones(test),
+            usize::MAX,
+            Engine::Matrix,
+            &rules,
+            Some(1_000_000_007),
+            &mut Cache::default(),
+        );
+        assert!(count < 1_000_000_007);
+    }
+
+    #[test]
+    fn test_realistic() {
+        let test = r"4 4841539 66 5279 49207 134 609568 0";
+        let rules = Rules::default();
+
+        let closure = Closure::new(&stones(test), &rules, CLOSURE_LIMIT).unwrap();
+        assert_eq!(closure.size(), 3956);
+        assert_eq!(closure.recurring(), 1763);
+        assert_eq!(closure.largest(), 409526509568);
+
+        for engine in [Engine::Memo, Engine::Histogram, Engine::Matrix] {
+            let count = process(
+                &stones(test),
+                75,
+                engine,
+                &rules,
+                None,
+                &mut Cache::default(),
+            );
+            assert_eq!(count, 253582809724830);
+        }
+
+        for (blinks, expected) in [(1000, 861407506)] {
+            for engine in [Engine::Histogram, Engine::Matrix] {
+                let count = process(
+                    &stones(test),
+                    blinks,
+                    engine,
+                    &rules,
+                    Some(1_000_000_007),
+                    &mut Cache::default(),
+                );
+                assert_eq!(count, expected, "{blinks}");
+            }
+        }
 
-        let result = process(test.as_bytes(), 25);
-        assert_eq!(result, 55312);
+        // Too many values to square for, and too many blinks to blink
+        // through, but the recurrence gets there.
+        let modulo = Some(1_000_000_007);
+        assert_eq!(closure.method(1000, modulo), Some(Method::Blink));
+        assert_eq!(closure.method(1_000_000, modulo), Some(Method::Recurrence));
+        assert_eq!(closure.method(1_000_000, Some(1_000_000_000)), None);
+        assert_eq!(closure.method(1_000_000, None), None);
+        assert_eq!(closure.count(&stones(test), 1_000_000, modulo), 907427190);
+    }
+
+    #[test]
+    fn test_recurrence() {
+        let prime = 1_000_000_007;
+        let fibonacci =
+            std::iter::successors(Some((0u64, 1u64)), |(a, b)| Some((*b, (a + b) % prime)))
+                .map(|(a, _)| a)
+                .take(100)
+                .collect::<Vec<_>>();
+
+        let recurrence = Recurrence::find(&fibonacci[..10], prime);
+        assert_eq!(recurrence.len(), 2);
+        for (n, term) in fibonacci.iter().enumerate() {
+            assert_eq!(recurrence.nth(n), *term, "{n}");
+        }
+        // F(10^18) mod 10^9 + 7
+        assert_eq!(recurrence.nth(1_000_000_000_000_000_000), 209783453);
+
+        assert_eq!(Recurrence::find(&[0; 8], prime).nth(1 << 40), 0);
+        assert_eq!(Recurrence::find(&[3, 0, 0, 0], prime).nth(1), 0);
+
+        let primes = [2, 3, 37, 1_000_000_007, 18446744073709551557];
+        assert!(primes.into_iter().all(is_prime));
+        let composites = [0, 1, 4, 561, 1_000_000_000, 3215031751, u64::MAX];
+        assert!(!composites.into_iter().any(is_prime));
+    }
+
+    #[test]
+    fn test_cache() {
+        for eviction in [Eviction::Lru, Eviction::Fifo] {
+            let mut cache = Cache::new(Some(2), eviction);
+            cache.insert(1, 'a');
+            cache.insert(2, 'b');
+            assert_eq!(cache.get(&1), Some('a'));
+
+            cache.insert(3, 'c');
+            let kept = match eviction {
+                Eviction::Lru => [Some('a'), None, Some('c')],
+                Eviction::Fifo => [None, Some('b'), Some('c')],
+            };
+            assert_eq!([1, 2, 3].map(|key| cache.get(&key)), kept);
+
+            let stats = cache.stats();
+            assert_eq!((stats.hits, stats.misses), (3, 1));
+            assert_eq!((stats.evictions, stats.size, stats.peak), (1, 2, 2));
+        }
+
+        let mut cache = Cache::new(Some(0), Eviction::Lru);
+        cache.insert(1, 'a');
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.stats().peak, 0);
+    }
+
+    #[test]
+    fn test_bounded_memo() {
+        let test = r"125 17";
+        let rules = Rules::default();
+
+        let mut unbounded = Cache::default();
+        let count = process(
+            &stones(test),
+            25,
+            Engine::Memo,
+            &rules,
+            None,
+            &mut unbounded,
+        );
+        assert_eq!(count, 55312);
+        assert_eq!(unbounded.stats().evictions, 0);
+
+        for eviction in [Eviction::Lru, Eviction::Fifo] {
+            for capacity in [0, 1, 16, 256] {
+                let mut cache = Cache::new(Some(capacity), eviction);
+                let count = process(&stones(test), 25, Engine::Memo, &rules, None, &mut cache);
+                assert_eq!(count, 55312);
+
+                let stats = cache.stats();
+                assert!(stats.peak <= capacity);
+                assert!(stats.misses > unbounded.stats().misses);
+            }
+        }
     }
 }
@@ -0,0 +1,146 @@
+use std::{collections::HashMap, io::Write};
+
+pub struct Encoder<W: Write> {
+    writer: W,
+    width: u16,
+    height: u16,
+    min_code_size: u8,
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(
+        mut writer: W,
+        width: u16,
+        height: u16,
+        palette: &[[u8; 3]],
+    ) -> std::io::Result<Self> {
+        assert!(!palette.is_empty() && palette.len() <= 256);
+
+        let bits = palette.len().next_power_of_two().trailing_zeros().max(1) as u8;
+
+        writer.write_all(b"GIF89a")?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        writer.write_all(&[0x80 | (bits - 1), 0, 0])?;
+
+        for i in 0..1 << bits {
+            writer.write_all(palette.get(i).unwrap_or(&[0, 0, 0]))?;
+        }
+
+        // Loop forever
+        writer.write_all(&[0x21, 0xff, 0x0b])?;
+        writer.write_all(b"NETSCAPE2.0")?;
+        writer.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+        Ok(Self {
+            writer,
+            width,
+            height,
+            min_code_size: bits.max(2),
+        })
+    }
+
+    pub fn frame(&mut self, pixels: &[u8], delay: u16) -> std::io::Result<()> {
+        assert_eq!(pixels.len(), self.width as usize * self.height as usize);
+
+        let delay = delay.to_le_bytes();
+        self.writer
+            .write_all(&[0x21, 0xf9, 0x04, 0x00, delay[0], delay[1], 0x00, 0x00])?;
+
+        self.writer.write_all(&[0x2c, 0, 0, 0, 0])?;
+        self.writer.write_all(&self.width.to_le_bytes())?;
+        self.writer.write_all(&self.height.to_le_bytes())?;
+        self.writer.write_all(&[0x00, self.min_code_size])?;
+
+        let data = lzw(pixels, self.min_code_size);
+        for block in data.chunks(255) {
+            self.writer.write_all(&[block.len() as u8])?;
+            self.writer.write_all(block)?;
+        }
+
+        self.writer.write_all(&[0x00])
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.writer.write_all(&[0x3b])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+fn lzw(pixels: &[u8], min_code_size: u8) -> Vec<u8> {
+    const MAX_CODE: u16 = 4095;
+
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+
+    let mut out = BitWriter::default();
+    let mut table = HashMap::new();
+    let mut next = end + 1;
+    let mut size = min_code_size + 1;
+
+    out.write(clear, size);
+
+    let mut pixels = pixels.iter();
+    let mut prefix = match pixels.next() {
+        Some(pixel) => *pixel as u16,
+        None => {
+            out.write(end, size);
+            return out.finish();
+        }
+    };
+
+    for &pixel in pixels {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+
+        out.write(prefix, size);
+
+        if next > MAX_CODE {
+            out.write(clear, size);
+            table.clear();
+            next = end + 1;
+            size = min_code_size + 1;
+        } else {
+            table.insert((prefix, pixel), next);
+            if next == 1 << size && size < 12 {
+                size += 1;
+            }
+            next += 1;
+        }
+
+        prefix = pixel as u16;
+    }
+
+    out.write(prefix, size);
+    out.write(end, size);
+    out.finish()
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.acc |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
@@ -1,16 +1,75 @@
 use std::{
     fs::File,
-    io::{BufReader, Read},
+    io::{BufRead, BufReader, BufWriter, Write},
+    time::Duration,
 };
 
+mod gif;
+
 fn main() {
-    let path = std::env::args()
-        .nth(1)
-        .expect("No input file path provided");
-    let file = File::open(path).expect("Cannot open file");
+    let options = Options::parse(std::env::args().skip(1));
+    let file = File::open(&options.path).expect("Cannot open file");
     let reader = BufReader::new(file);
 
-    let mut pos = None;
+    let mut grid = parse(reader, options.turning);
+
+    if options.animate || options.gif.is_some() {
+        animate(grid.clone(), &options);
+    }
+
+    println!("Sum: {}", grid.solve());
+
+    let looping = grid.looping();
+    if looping > 0 {
+        println!("Looping guards: {looping}");
+    }
+}
+
+struct Options {
+    path: String,
+    animate: bool,
+    gif: Option<String>,
+    delay: Duration,
+    turning: Turning,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut path = None;
+        let mut animate = false;
+        let mut gif = None;
+        let mut delay = Duration::from_millis(50);
+        let mut turning = Turning::Right;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--animate" => animate = true,
+                "--gif" => gif = Some(args.next().expect("No GIF output path provided")),
+                "--delay" => {
+                    let ms = args.next().expect("No delay provided");
+                    delay = Duration::from_millis(ms.parse().expect("Cannot parse delay"));
+                }
+                "--turn" => {
+                    let policy = args.next().expect("No turning policy provided");
+                    turning = Turning::try_from(&policy).expect("Invalid turning policy");
+                }
+                _ if path.is_none() => path = Some(arg),
+                _ => panic!("Unexpected argument: {arg}"),
+            }
+        }
+
+        Self {
+            path: path.expect("No input file path provided"),
+            animate,
+            gif,
+            delay,
+            turning,
+        }
+    }
+}
+
+fn parse(reader: impl BufRead, turning: Turning) -> Grid {
+    let mut guards = Vec::new();
     let mut first_newline = None;
     let mut grid = Vec::new();
     let mut len = 0;
@@ -18,9 +77,7 @@ fn main() {
     for byte in reader.bytes().map(|byte| byte.expect("Cannot read byte")) {
         let obstacle = match (Dir::try_from(byte), byte) {
             (Some(dir), _) => {
-                if let Some(_) = pos.replace(Pos { offset: len, dir }) {
-                    panic!("Multiple starting positions");
-                }
+                guards.push(Guard::new(Pos { offset: len, dir }));
                 false
             }
 
@@ -41,32 +98,114 @@ fn main() {
         grid.push(obstacle);
     }
 
-    let pos = pos.expect("No starting position");
+    assert!(!guards.is_empty(), "No starting position");
     let cols = first_newline.expect("No newline found");
 
-    let mut grid = Grid::new(grid, cols, pos);
-    println!("Sum: {}", grid.solve());
+    Grid::new(grid, cols, guards, turning)
 }
 
+fn animate(mut grid: Grid, options: &Options) {
+    let mut trail = Trail::new(&grid);
+
+    let mut gif = options.gif.as_ref().map(|path| {
+        let file = File::create(path).expect("Cannot create GIF file");
+        let width = u16::try_from(grid.cols * Tile::SCALE).expect("Map too wide for GIF");
+        let height = u16::try_from(grid.rows() * Tile::SCALE).expect("Map too tall for GIF");
+        gif::Encoder::new(BufWriter::new(file), width, height, &Tile::PALETTE)
+            .expect("Cannot write GIF header")
+    });
+    // GIF delays are in hundredths of a second, up to about 11 minutes
+    let delay = u16::try_from(options.delay.as_millis() / 10).unwrap_or(u16::MAX);
+
+    loop {
+        let tiles = grid.render(&trail);
+
+        if options.animate {
+            let mut stdout = std::io::stdout().lock();
+            write!(stdout, "\x1b[H\x1b[2J").expect("Cannot write to stdout");
+            for row in tiles.chunks(grid.cols) {
+                let line = row.iter().map(Tile::as_char).collect::<String>();
+                writeln!(stdout, "{line}").expect("Cannot write to stdout");
+            }
+            stdout.flush().expect("Cannot flush stdout");
+            std::thread::sleep(options.delay);
+        }
+
+        if let Some(gif) = gif.as_mut() {
+            gif.frame(&Tile::pixels(&tiles, grid.cols), delay)
+                .expect("Cannot write GIF frame");
+        }
+
+        let moves = grid.step();
+        if moves.is_empty() {
+            break;
+        }
+
+        for (guard, from, step) in moves {
+            trail.record(from, &grid.guards[guard].pos, &step);
+        }
+    }
+
+    if let Some(gif) = gif {
+        gif.finish().expect("Cannot finish GIF");
+    }
+}
+
+#[derive(Clone)]
 struct Grid {
     grid: Vec<bool>,
     cols: usize,
-    pos: Pos,
+    guards: Vec<Guard>,
+    turning: Turning,
 }
 
 impl Grid {
-    fn new(grid: Vec<bool>, cols: usize, pos: Pos) -> Self {
-        Self { grid, cols, pos }
+    fn new(grid: Vec<bool>, cols: usize, guards: Vec<Guard>, turning: Turning) -> Self {
+        let mut grid = Self {
+            grid,
+            cols,
+            guards,
+            turning,
+        };
+
+        for guard in 0..grid.guards.len() {
+            grid.mark_seen(guard);
+        }
+
+        grid
     }
 
-    fn pos(&self) -> (usize, usize) {
-        let y = self.pos.offset / self.cols;
-        let x = self.pos.offset % self.cols;
-        (x, y)
+    fn rows(&self) -> usize {
+        self.grid.len() / self.cols
     }
 
-    fn set_pos(&mut self, x: usize, y: usize) {
-        self.pos.offset = y * self.cols + x;
+    fn pos(&self, guard: usize) -> (usize, usize) {
+        let offset = self.guards[guard].pos.offset;
+        (offset % self.cols, offset / self.cols)
+    }
+
+    fn set_pos(&mut self, guard: usize, x: usize, y: usize) {
+        self.guards[guard].pos.offset = y * self.cols + x;
+    }
+
+    /// Records the guard's current state, returning `false` if it has been in
+    /// this exact state before and is therefore walking in a loop.
+    fn mark_seen(&mut self, guard: usize) -> bool {
+        let guard = &mut self.guards[guard];
+        if guard.seen.is_empty() {
+            guard.seen = vec![0; self.grid.len()];
+        }
+
+        let phase = match self.turning {
+            Turning::Alternating => guard.turns % 2,
+            _ => 0,
+        };
+        let bit = 1 << (guard.pos.dir.index() + 4 * phase);
+
+        let seen = &mut guard.seen[guard.pos.offset];
+        let new = *seen & bit == 0;
+        *seen |= bit;
+        new
     }
 
     fn get(&self, x: usize, y: usize) -> Option<bool> {
@@ -77,10 +216,10 @@ impl Grid {
         Some(self.grid[y * self.cols + x])
     }
 
-    fn move_next(&mut self) -> Move {
-        let (x, y) = self.pos();
+    fn move_next(&mut self, guard: usize) -> Move {
+        let (x, y) = self.pos(guard);
 
-        let (x, y) = match self.pos.dir {
+        let (x, y) = match self.guards[guard].pos.dir {
             Dir::Up => (x, if y == 0 { return Move::Done } else { y - 1 }),
             Dir::Down => (x, y + 1),
             Dir::Left => (if x == 0 { return Move::Done } else { x - 1 }, y),
@@ -90,28 +229,239 @@ impl Grid {
         match self.get(x, y) {
             None => Move::Done,
             Some(true) => {
-                self.pos.dir = self.pos.dir.turn_right();
+                let guard = &mut self.guards[guard];
+                guard.pos.dir = self.turning.apply(guard.pos.dir, guard.turns);
+                guard.turns += 1;
                 Move::Turn
             }
             _ => {
-                self.set_pos(x, y);
+                self.set_pos(guard, x, y);
                 Move::Progress
             }
         }
     }
 
+    /// Moves every guard still on patrol once, returning which guard moved,
+    /// where it moved from and how. Empty once every guard has left the map
+    /// or been caught in a loop.
+    fn step(&mut self) -> Vec<(usize, Pos, Move)> {
+        let mut moves = Vec::new();
+
+        for guard in 0..self.guards.len() {
+            if self.guards[guard].status != Status::Patrolling {
+                continue;
+            }
+
+            let from = self.guards[guard].pos;
+            let step = self.move_next(guard);
+
+            match step {
+                Move::Done => self.guards[guard].status = Status::Left,
+                _ if !self.mark_seen(guard) => self.guards[guard].status = Status::Looping,
+                _ => {}
+            }
+
+            moves.push((guard, from, step));
+        }
+
+        moves
+    }
+
     fn solve(&mut self) -> usize {
         let mut visited = vec![0; self.grid.len()];
-        visited[self.pos.offset] = 1;
+        for guard in &self.guards {
+            visited[guard.pos.offset] = 1;
+        }
+
         loop {
-            match self.move_next() {
-                Move::Done => break visited.iter().sum(),
-                Move::Progress => {
-                    visited[self.pos.offset] = 1;
+            let moves = self.step();
+            if moves.is_empty() {
+                break visited.iter().sum();
+            }
+
+            for (guard, _, step) in moves {
+                if let Move::Progress = step {
+                    visited[self.guards[guard].pos.offset] = 1;
+                }
+            }
+        }
+    }
+
+    fn looping(&self) -> usize {
+        self.guards
+            .iter()
+            .filter(|guard| guard.status == Status::Looping)
+            .count()
+    }
+
+    fn render(&self, trail: &Trail) -> Vec<Tile> {
+        let mut tiles = self
+            .grid
+            .iter()
+            .zip(trail.0.iter())
+            .map(|(obstacle, path)| match (obstacle, *path) {
+                (true, _) => Tile::Obstacle,
+                (_, Trail::VERTICAL) => Tile::Vertical,
+                (_, Trail::HORIZONTAL) => Tile::Horizontal,
+                (_, Trail::BOTH) => Tile::Cross,
+                _ => Tile::Empty,
+            })
+            .collect::<Vec<_>>();
+
+        // Guards that have left stay drawn where they were last seen
+        for guard in &self.guards {
+            tiles[guard.pos.offset] = Tile::Guard(guard.pos.dir);
+        }
+
+        tiles
+    }
+}
+
+/// Which axes the guard has travelled along in each cell, as drawn in the
+/// puzzle text: `|` for vertical, `-` for horizontal and `+` for both.
+struct Trail(Vec<u8>);
+
+impl Trail {
+    const VERTICAL: u8 = 1;
+    const HORIZONTAL: u8 = 2;
+    const BOTH: u8 = Self::VERTICAL | Self::HORIZONTAL;
+
+    fn new(grid: &Grid) -> Self {
+        let mut trail = vec![0; grid.grid.len()];
+        for guard in &grid.guards {
+            trail[guard.pos.offset] |= Self::axis(guard.pos.dir);
+        }
+        Self(trail)
+    }
+
+    fn axis(dir: Dir) -> u8 {
+        match dir {
+            Dir::Up | Dir::Down => Self::VERTICAL,
+            Dir::Left | Dir::Right => Self::HORIZONTAL,
+        }
+    }
+
+    fn record(&mut self, from: Pos, to: &Pos, step: &Move) {
+        match step {
+            Move::Turn => self.0[to.offset] = Self::BOTH,
+            Move::Progress => {
+                self.0[from.offset] |= Self::axis(to.dir);
+                self.0[to.offset] |= Self::axis(to.dir);
+            }
+            Move::Done => {}
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Tile {
+    Empty,
+    Obstacle,
+    Vertical,
+    Horizontal,
+    Cross,
+    Guard(Dir),
+}
+
+impl Tile {
+    /// Width and height in pixels of a single cell in the GIF output.
+    const SCALE: usize = 4;
+
+    const PALETTE: [[u8; 3]; 4] = [
+        [0x0f, 0x0f, 0x23],
+        [0xcc, 0xcc, 0xcc],
+        [0x00, 0x99, 0x00],
+        [0xff, 0xff, 0x66],
+    ];
+
+    fn as_char(&self) -> char {
+        match self {
+            Tile::Empty => '.',
+            Tile::Obstacle => '#',
+            Tile::Vertical => '|',
+            Tile::Horizontal => '-',
+            Tile::Cross => '+',
+            Tile::Guard(dir) => dir.as_char(),
+        }
+    }
+
+    fn color(&self) -> u8 {
+        match self {
+            Tile::Empty => 0,
+            Tile::Obstacle => 1,
+            Tile::Vertical | Tile::Horizontal | Tile::Cross => 2,
+            Tile::Guard(_) => 3,
+        }
+    }
+
+    fn pixels(tiles: &[Tile], cols: usize) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(tiles.len() * Self::SCALE * Self::SCALE);
+        for row in tiles.chunks(cols) {
+            for _ in 0..Self::SCALE {
+                for tile in row {
+                    pixels.extend(std::iter::repeat_n(tile.color(), Self::SCALE));
                 }
-                Move::Turn => {}
             }
         }
+        pixels
+    }
+}
+
+#[derive(Clone)]
+struct Guard {
+    pos: Pos,
+    turns: usize,
+    status: Status,
+    seen: Vec<u8>,
+}
+
+impl Guard {
+    fn new(pos: Pos) -> Self {
+        Self {
+            pos,
+            turns: 0,
+            status: Status::Patrolling,
+            seen: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Patrolling,
+    Left,
+    Looping,
+}
+
+/// What a guard does on reaching an obstacle.
+#[derive(Clone, Copy)]
+enum Turning {
+    Right,
+    Left,
+    Reverse,
+    /// Right on the first obstacle, left on the second, and so on.
+    Alternating,
+}
+
+impl Turning {
+    fn try_from(s: &str) -> Option<Self> {
+        match s {
+            "right" => Some(Self::Right),
+            "left" => Some(Self::Left),
+            "reverse" => Some(Self::Reverse),
+            "alternating" => Some(Self::Alternating),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, dir: Dir, turns: usize) -> Dir {
+        match self {
+            Self::Right => dir.turn_right(),
+            Self::Left => dir.turn_left(),
+            Self::Reverse => dir.reverse(),
+            Self::Alternating if turns.is_multiple_of(2) => dir.turn_right(),
+            Self::Alternating => dir.turn_left(),
+        }
     }
 }
 
@@ -140,6 +490,24 @@ impl Dir {
         }
     }
 
+    fn as_char(&self) -> char {
+        match self {
+            Self::Up => '^',
+            Self::Down => 'v',
+            Self::Left => '<',
+            Self::Right => '>',
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Self::Up => 0,
+            Self::Down => 1,
+            Self::Left => 2,
+            Self::Right => 3,
+        }
+    }
+
     fn turn_right(&self) -> Self {
         match self {
             Self::Up => Self::Right,
@@ -148,6 +516,24 @@ impl Dir {
             Self::Right => Self::Down,
         }
     }
+
+    fn turn_left(&self) -> Self {
+        match self {
+            Self::Up => Self::Left,
+            Self::Down => Self::Right,
+            Self::Left => Self::Down,
+            Self::Right => Self::Up,
+        }
+    }
+
+    fn reverse(&self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -155,3 +541,238 @@ struct Pos {
     offset: usize,
     dir: Dir,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r"
+        ....#.....
+        .........#
+        ..........
+        ..#.......
+        .......#..
+        ..........
+        .#..^.....
+        ........#.
+        #.........
+        ......#...
+    ";
+
+    fn example(turning: Turning) -> Grid {
+        let input = EXAMPLE.trim().replace(' ', "");
+        parse(input.as_bytes(), turning)
+    }
+
+    #[test]
+    fn test() {
+        assert_eq!(example(Turning::Right).solve(), 41);
+    }
+
+    #[test]
+    fn test_render() {
+        let mut grid = example(Turning::Right);
+        let mut trail = Trail::new(&grid);
+
+        loop {
+            let moves = grid.step();
+            if moves.is_empty() {
+                break;
+            }
+
+            for (guard, from, step) in moves {
+                trail.record(from, &grid.guards[guard].pos, &step);
+            }
+        }
+
+        let rendered = grid
+            .render(&trail)
+            .chunks(grid.cols)
+            .map(|row| row.iter().map(Tile::as_char).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let expected = r"
+            ....#.....
+            ....+---+#
+            ....|...|.
+            ..#.|...|.
+            ..+-+-+#|.
+            ..|.|.|.|.
+            .#+-+-+-+.
+            .+----++#.
+            #+----+|..
+            ......#v..
+        "
+        .trim()
+        .replace(' ', "");
+
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn test_multiple_guards() {
+        let input = "#....\n..^..\n....<";
+        let mut grid = parse(input.as_bytes(), Turning::Right);
+
+        assert_eq!(grid.solve(), 7);
+        assert_eq!(grid.looping(), 0);
+    }
+
+    #[test]
+    fn test_turning() {
+        let input = ".#...\n.^...";
+
+        assert_eq!(parse(input.as_bytes(), Turning::Right).solve(), 4);
+        assert_eq!(parse(input.as_bytes(), Turning::Left).solve(), 2);
+        assert_eq!(parse(input.as_bytes(), Turning::Alternating).solve(), 4);
+
+        let input = "..#..\n....#\n..^..\n.....";
+        assert_eq!(parse(input.as_bytes(), Turning::Right).solve(), 5);
+        assert_eq!(parse(input.as_bytes(), Turning::Alternating).solve(), 4);
+
+        let input = "#\n.\n^\n#";
+        let mut grid = parse(input.as_bytes(), Turning::Reverse);
+
+        assert_eq!(grid.solve(), 2);
+        assert_eq!(grid.looping(), 1);
+    }
+
+    /// Decodes a GIF as written by [`gif::Encoder`] into the pixels of each
+    /// frame.
+    fn decode_gif(bytes: &[u8]) -> Vec<Vec<u8>> {
+        assert_eq!(&bytes[..6], b"GIF89a");
+        let palette = 3 << ((bytes[10] & 0x07) + 1);
+        let mut bytes = bytes[13 + palette..].iter().copied();
+        let mut frames = Vec::new();
+
+        let sub_blocks = |bytes: &mut dyn Iterator<Item = u8>| {
+            let mut data = Vec::new();
+            loop {
+                match bytes.next().expect("Truncated GIF") {
+                    0 => return data,
+                    len => data.extend(bytes.take(len as usize)),
+                }
+            }
+        };
+
+        loop {
+            match bytes.next().expect("Truncated GIF") {
+                0x21 => {
+                    bytes.next();
+                    sub_blocks(&mut bytes);
+                }
+                0x2c => {
+                    let descriptor = bytes.by_ref().take(9).collect::<Vec<_>>();
+                    assert_eq!(descriptor[8], 0, "Local palettes aren't written");
+                    let min_code_size = bytes.next().expect("Truncated GIF");
+                    frames.push(decode_lzw(&sub_blocks(&mut bytes), min_code_size));
+                }
+                0x3b => return frames,
+                block => panic!("Unexpected block {block:#x}"),
+            }
+        }
+    }
+
+    /// A textbook GIF LZW decoder, to check the encoder against.
+    fn decode_lzw(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let end = clear + 1;
+        let reset = || (0..clear).map(|i| vec![i as u8]).chain([vec![], vec![]]);
+
+        let mut table = reset().collect::<Vec<_>>();
+        let mut size = min_code_size + 1;
+        let mut prev: Option<Vec<u8>> = None;
+        let mut pixels = Vec::new();
+        let mut bit = 0;
+
+        loop {
+            let code = (0..size as usize)
+                .map(|i| (data[(bit + i) / 8] as usize >> ((bit + i) % 8) & 1) << i)
+                .sum::<usize>();
+            bit += size as usize;
+
+            if code == clear {
+                table = reset().collect();
+                size = min_code_size + 1;
+                prev = None;
+                continue;
+            }
+            if code == end {
+                return pixels;
+            }
+
+            let entry = match (table.get(code), &prev) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(prev)) if code == table.len() => [&prev[..], &prev[..1]].concat(),
+                _ => panic!("Invalid code {code}"),
+            };
+            pixels.extend(&entry);
+
+            if let Some(prev) = prev.filter(|_| table.len() < 4096) {
+                table.push([&prev[..], &entry[..1]].concat());
+            }
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+            prev = Some(entry);
+        }
+    }
+
+    fn encode_gif(width: u16, height: u16, palette: &[[u8; 3]], frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut encoder = gif::Encoder::new(Vec::new(), width, height, palette).unwrap();
+        for frame in frames {
+            encoder.frame(frame, 10).unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_gif() {
+        let gif = encode_gif(2, 2, &[[0, 0, 0], [255, 255, 255]], &[vec![0, 1, 1, 0]]);
+
+        let mut expected = b"GIF89a".to_vec();
+        expected.extend([2, 0, 2, 0, 0x80, 0, 0]);
+        expected.extend([0, 0, 0, 255, 255, 255]);
+        expected.extend([0x21, 0xff, 0x0b]);
+        expected.extend(b"NETSCAPE2.0");
+        expected.extend([0x03, 0x01, 0x00, 0x00, 0x00]);
+        expected.extend([0x21, 0xf9, 0x04, 0x00, 10, 0, 0x00, 0x00]);
+        expected.extend([0x2c, 0, 0, 0, 0, 2, 0, 2, 0, 0x00, 2]);
+        // Clear, 0, 1 and 1 in 3 bits, then 0 and end in 4 bits
+        expected.extend([3, 0x44, 0x02, 0x05, 0]);
+        expected.push(0x3b);
+
+        assert_eq!(gif, expected);
+    }
+
+    #[test]
+    fn test_gif_round_trip() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        // Noise fills the code table and forces clear codes, runs grow long
+        // codes, and a ramp through every colour has no runs at all.
+        let frames = vec![
+            (0..256 * 256)
+                .map(|_| (next() % 4) as u8)
+                .collect::<Vec<_>>(),
+            (0..256 * 256).map(|i| (i / 1000 % 4) as u8).collect(),
+            (0..256 * 256).map(|i| (i % 4) as u8).collect(),
+            vec![0; 256 * 256],
+        ];
+
+        let gif = encode_gif(256, 256, &Tile::PALETTE, &frames);
+        assert_eq!(decode_gif(&gif), frames);
+
+        let palette = [[0, 0, 0]; 256];
+        let frame = (0..64 * 64).map(|_| next() as u8).collect::<Vec<_>>();
+        let gif = encode_gif(64, 64, &palette, std::slice::from_ref(&frame));
+        assert_eq!(decode_gif(&gif), [frame]);
+    }
+}
@@ -1,17 +1,35 @@
 use std::{
     collections::HashSet,
     fs::File,
-    io::{BufReader, Read},
+    io::{BufRead, BufReader},
 };
 
 fn main() {
-    let path = std::env::args()
-        .nth(1)
-        .expect("No input file path provided");
+    let mut path = None;
+    let mut turning = Turning::Right;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--turn" => {
+                let policy = args.next().expect("No turning policy provided");
+                turning = Turning::try_from(&policy).expect("Invalid turning policy");
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("Unexpected argument: {arg}"),
+        }
+    }
+
+    let path = path.expect("No input file path provided");
     let file = File::open(path).expect("Cannot open file");
     let reader = BufReader::new(file);
 
-    let mut pos = None;
+    let sum = parse(reader, turning).find_loops();
+    println!("Sum: {}", sum);
+}
+
+fn parse(reader: impl BufRead, turning: Turning) -> Grid {
+    let mut guards = Vec::new();
     let mut first_newline = None;
     let mut grid = Vec::new();
     let mut len = 0;
@@ -19,9 +37,7 @@ fn main() {
     for byte in reader.bytes().map(|byte| byte.expect("Cannot read byte")) {
         let obstacle = match (Dir::try_from(byte), byte) {
             (Some(dir), _) => {
-                if let Some(_) = pos.replace(Pos { offset: len, dir }) {
-                    panic!("Multiple starting positions");
-                }
+                guards.push(Guard::new(Pos { offset: len, dir }));
                 false
             }
 
@@ -42,36 +58,27 @@ fn main() {
         grid.push(obstacle);
     }
 
-    let pos = pos.expect("No starting position");
+    assert!(!guards.is_empty(), "No starting position");
     let cols = first_newline.expect("No newline found");
 
-    let sum = Grid::new(grid, cols, pos).find_loops();
-    println!("Sum: {}", sum);
+    Grid::new(grid, cols, guards, turning)
 }
 
 struct Grid {
     grid: Vec<bool>,
     cols: usize,
-    pos: Pos,
+    guards: Vec<Guard>,
+    turning: Turning,
 }
 
 impl Grid {
-    fn new(grid: Vec<bool>, cols: usize, pos: Pos) -> Self {
-        Self { grid, cols, pos }
-    }
-
-    fn pos(&self) -> (usize, usize) {
-        let y = self.pos.offset / self.cols;
-        let x = self.pos.offset % self.cols;
-        (x, y)
-    }
-
-    fn set_pos(&mut self, x: usize, y: usize) {
-        self.pos.offset = y * self.cols + x;
-    }
-
-    fn set_obstacle(&mut self, x: usize, y: usize, value: bool) {
-        self.grid[y * self.cols + x] = value;
+    fn new(grid: Vec<bool>, cols: usize, guards: Vec<Guard>, turning: Turning) -> Self {
+        Self {
+            grid,
+            cols,
+            guards,
+            turning,
+        }
     }
 
     fn get(&self, x: usize, y: usize) -> Option<bool> {
@@ -82,10 +89,11 @@ impl Grid {
         Some(self.grid[y * self.cols + x])
     }
 
-    fn peek_next(&self) -> Move {
-        let (x, y) = self.pos();
+    fn peek_next(&self, guard: &Guard) -> Move {
+        let x = guard.pos.offset % self.cols;
+        let y = guard.pos.offset / self.cols;
 
-        let (x, y) = match self.pos.dir {
+        let (x, y) = match guard.pos.dir {
             Dir::Up => (x, if y == 0 { return Move::Done } else { y - 1 }),
             Dir::Down => (x, y + 1),
             Dir::Left => (if x == 0 { return Move::Done } else { x - 1 }, y),
@@ -94,59 +102,137 @@ impl Grid {
 
         match self.get(x, y) {
             None => Move::Done,
-            Some(true) => Move::Turn(self.pos.dir.turn_right()),
-            _ => Move::Progress(x, y),
+            Some(true) => Move::Turn(self.turning.apply(guard.pos.dir, guard.turns)),
+            _ => Move::Progress(y * self.cols + x),
+        }
+    }
+
+    /// What a guard's future depends on: where it is, which way it faces and,
+    /// when alternating, which way it turns next.
+    fn state(&self, guard: &Guard) -> (Pos, usize) {
+        match self.turning {
+            Turning::Alternating => (guard.pos, guard.turns % 2),
+            _ => (guard.pos, 0),
         }
     }
 
-    fn loops(&mut self) -> bool {
-        let mut visited = std::collections::HashSet::new();
-        visited.insert(self.pos);
+    fn loops(&self, mut guard: Guard) -> bool {
+        let mut visited = HashSet::new();
+        visited.insert(self.state(&guard));
         loop {
-            match self.peek_next() {
+            match self.peek_next(&guard) {
                 Move::Done => break false,
-                Move::Progress(x, y) => {
-                    self.set_pos(x, y);
-                    if !visited.insert(self.pos) {
-                        break true;
-                    }
-                }
+                Move::Progress(offset) => guard.pos.offset = offset,
                 Move::Turn(dir) => {
-                    self.pos.dir = dir;
+                    guard.pos.dir = dir;
+                    guard.turns += 1;
                 }
             }
+
+            if !visited.insert(self.state(&guard)) {
+                break true;
+            }
         }
     }
 
+    /// Counts the cells where a new obstacle would trap a guard that
+    /// otherwise leaves the map in a loop. Guards already walking in a loop
+    /// are left out, and no obstacle goes where a guard starts.
     fn find_loops(&mut self) -> usize {
+        let leaving = self
+            .guards
+            .iter()
+            .filter(|guard| !self.loops(**guard))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let mut tried = vec![false; self.grid.len()];
+        for guard in &self.guards {
+            tried[guard.pos.offset] = true;
+        }
+
         let mut sum = 0;
-        let mut visited = HashSet::new();
-        loop {
-            match self.peek_next() {
-                Move::Done => break sum,
-                Move::Progress(x, y) => {
-                    let dir = self.pos.dir;
-                    if visited.insert((x, y)) {
-                        self.set_obstacle(x, y, true);
-                        if self.loops() {
-                            sum += 1;
+        for (i, mut guard) in leaving.iter().copied().enumerate() {
+            loop {
+                match self.peek_next(&guard) {
+                    Move::Done => break,
+                    Move::Progress(offset) => {
+                        if !std::mem::replace(&mut tried[offset], true) {
+                            self.grid[offset] = true;
+                            // This is the first time this guard gets here, so
+                            // it can carry on from where it is, but the others
+                            // may have passed by earlier.
+                            let looping = self.loops(guard)
+                                || leaving
+                                    .iter()
+                                    .enumerate()
+                                    .any(|(j, other)| j != i && self.loops(*other));
+                            if looping {
+                                sum += 1;
+                            }
+                            self.grid[offset] = false;
                         }
+                        guard.pos.offset = offset;
+                    }
+                    Move::Turn(dir) => {
+                        guard.pos.dir = dir;
+                        guard.turns += 1;
                     }
-                    self.set_obstacle(x, y, false);
-                    self.pos.dir = dir;
-                    self.set_pos(x, y);
-                }
-                Move::Turn(dir) => {
-                    self.pos.dir = dir;
                 }
             }
         }
+
+        sum
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Guard {
+    pos: Pos,
+    turns: usize,
+}
+
+impl Guard {
+    fn new(pos: Pos) -> Self {
+        Self { pos, turns: 0 }
+    }
+}
+
+/// What a guard does on reaching an obstacle.
+#[derive(Clone, Copy)]
+enum Turning {
+    Right,
+    Left,
+    Reverse,
+    /// Right on the first obstacle, left on the second, and so on.
+    Alternating,
+}
+
+impl Turning {
+    fn try_from(s: &str) -> Option<Self> {
+        match s {
+            "right" => Some(Self::Right),
+            "left" => Some(Self::Left),
+            "reverse" => Some(Self::Reverse),
+            "alternating" => Some(Self::Alternating),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, dir: Dir, turns: usize) -> Dir {
+        match self {
+            Self::Right => dir.turn_right(),
+            Self::Left => dir.turn_left(),
+            Self::Reverse => dir.reverse(),
+            Self::Alternating if turns.is_multiple_of(2) => dir.turn_right(),
+            Self::Alternating => dir.turn_left(),
+        }
     }
 }
 
 enum Move {
     Turn(Dir),
-    Progress(usize, usize),
+    Progress(usize),
     Done,
 }
 
@@ -177,6 +263,24 @@ impl Dir {
             Self::Right => Self::Down,
         }
     }
+
+    fn turn_left(&self) -> Self {
+        match self {
+            Self::Up => Self::Left,
+            Self::Down => Self::Right,
+            Self::Left => Self::Down,
+            Self::Right => Self::Up,
+        }
+    }
+
+    fn reverse(&self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
@@ -184,3 +288,60 @@ struct Pos {
     offset: usize,
     dir: Dir,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r"
+        ....#.....
+        .........#
+        ..........
+        ..#.......
+        .......#..
+        ..........
+        .#..^.....
+        ........#.
+        #.........
+        ......#...
+    ";
+
+    fn example(turning: Turning) -> Grid {
+        let input = EXAMPLE.trim().replace(' ', "");
+        parse(input.as_bytes(), turning)
+    }
+
+    #[test]
+    fn test() {
+        assert_eq!(example(Turning::Right).find_loops(), 6);
+    }
+
+    #[test]
+    fn test_multiple_guards() {
+        // Each guard alone can be trapped in one place, and both together in
+        // either.
+        let input = "#.###.\n##.#..\n...<#.\n..#v.#\n....#.";
+        assert_eq!(parse(input.as_bytes(), Turning::Right).find_loops(), 2);
+        let first = "#.###.\n##.#..\n...<#.\n..#..#\n....#.";
+        assert_eq!(parse(first.as_bytes(), Turning::Right).find_loops(), 1);
+
+        // The first guard walks in a loop already, so only the second counts.
+        let input = "#..#..\n..#...\n.#v.#.\n.#.#v#\n..#...";
+        let mut grid = parse(input.as_bytes(), Turning::Right);
+        assert!(grid.loops(grid.guards[0]));
+        assert!(!grid.loops(grid.guards[1]));
+        assert_eq!(grid.find_loops(), 1);
+    }
+
+    #[test]
+    fn test_turning() {
+        for (turning, expected) in [
+            (Turning::Right, 6),
+            (Turning::Left, 0),
+            (Turning::Reverse, 3),
+            (Turning::Alternating, 2),
+        ] {
+            assert_eq!(example(turning).find_loops(), expected);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+/// Base of each limb, chosen so that parsing, printing and counting decimal
+/// digits work limb by limb.
+const BASE: u64 = 1_000_000_000;
+const BASE_DIGITS: u32 = 9;
+
+/// Arbitrary-precision unsigned integer, stored as little-endian base 10^9
+/// limbs with no trailing zero limbs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BigUint(Vec<u32>);
+
+impl BigUint {
+    pub fn from_u64(mut value: u64) -> Self {
+        let mut limbs = Vec::new();
+        while value > 0 {
+            limbs.push((value % BASE) as u32);
+            value /= BASE;
+        }
+        Self(limbs)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn digits(&self) -> u32 {
+        match self.0.last() {
+            None => 1,
+            Some(last) => {
+                (self.0.len() as u32 - 1) * BASE_DIGITS + last.checked_ilog10().unwrap_or(0) + 1
+            }
+        }
+    }
+
+    pub fn pow10(exp: u32) -> Self {
+        let mut limbs = vec![0; (exp / BASE_DIGITS) as usize];
+        limbs.push(10u32.pow(exp % BASE_DIGITS));
+        Self(limbs)
+    }
+
+    pub fn add(&self, rhs: &Self) -> Self {
+        let mut limbs = Vec::with_capacity(self.0.len().max(rhs.0.len()) + 1);
+        let mut carry = 0;
+
+        for i in 0..self.0.len().max(rhs.0.len()) {
+            let sum = carry
+                + self.0.get(i).copied().unwrap_or(0) as u64
+                + rhs.0.get(i).copied().unwrap_or(0) as u64;
+            limbs.push((sum % BASE) as u32);
+            carry = sum / BASE;
+        }
+
+        if carry > 0 {
+            limbs.push(carry as u32);
+        }
+
+        Self(limbs)
+    }
+
+    pub fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        if self < rhs {
+            return None;
+        }
+
+        let mut limbs = Vec::with_capacity(self.0.len());
+        let mut borrow = 0;
+
+        for (i, limb) in self.0.iter().enumerate() {
+            let sub = rhs.0.get(i).copied().unwrap_or(0) as i64 + borrow;
+            let mut diff = *limb as i64 - sub;
+            borrow = 0;
+            if diff < 0 {
+                diff += BASE as i64;
+                borrow = 1;
+            }
+            limbs.push(diff as u32);
+        }
+
+        Some(Self(limbs).normalized())
+    }
+
+    pub fn mul(&self, rhs: &Self) -> Self {
+        if self.is_zero() || rhs.is_zero() {
+            return Self::default();
+        }
+
+        let mut limbs = vec![0u64; self.0.len() + rhs.0.len()];
+
+        for (i, a) in self.0.iter().enumerate() {
+            let mut carry = 0;
+            for (j, b) in rhs.0.iter().enumerate() {
+                let product = limbs[i + j] + *a as u64 * *b as u64 + carry;
+                limbs[i + j] = product % BASE;
+                carry = product / BASE;
+            }
+            limbs[i + rhs.0.len()] += carry;
+        }
+
+        Self(limbs.into_iter().map(|limb| limb as u32).collect()).normalized()
+    }
+
+    /// Divides by a machine word, returning the quotient and remainder.
+    pub fn div_rem(&self, rhs: u64) -> (Self, u64) {
+        assert_ne!(rhs, 0, "Division by zero");
+
+        let mut limbs = vec![0; self.0.len()];
+        let mut rem = 0u128;
+
+        for (i, limb) in self.0.iter().enumerate().rev() {
+            rem = rem * BASE as u128 + *limb as u128;
+            limbs[i] = (rem / rhs as u128) as u32;
+            rem %= rhs as u128;
+        }
+
+        (Self(limbs).normalized(), rem as u64)
+    }
+
+    fn normalized(mut self) -> Self {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+        self
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for BigUint {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err("Invalid number");
+        }
+
+        let limbs = s
+            .as_bytes()
+            .rchunks(BASE_DIGITS as usize)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0, |limb, digit| limb * 10 + (digit - b'0') as u32)
+            })
+            .collect();
+
+        Ok(Self(limbs).normalized())
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut limbs = self.0.iter().rev();
+        match limbs.next() {
+            None => write!(f, "0"),
+            Some(first) => {
+                write!(f, "{first}")?;
+                limbs.try_for_each(|limb| write!(f, "{limb:09}"))
+            }
+        }
+    }
+}
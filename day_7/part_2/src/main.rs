@@ -1,48 +1,989 @@
 use std::{
+    fmt::Display,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, Write},
+    ops::ControlFlow,
 };
 
+use big::BigUint;
+
+mod big;
+
 fn main() {
-    let path = std::env::args()
-        .nth(1)
-        .expect("No input file path provided");
+    let mut path = None;
+    let mut mode = Mode::First;
+    let mut evaluation = Evaluation::LeftToRight;
+    let mut compare = false;
+    let mut concat = Concat::default();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--all" => mode = Mode::All,
+            "--count" => mode = Mode::Count,
+            "--precedence" => evaluation = Evaluation::Precedence,
+            "--compare" => compare = true,
+            "--concat-precedence" => {
+                let precedence = args.next().expect("No precedence provided");
+                concat.precedence = precedence.parse().expect("Cannot parse precedence");
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("Unexpected argument: {arg}"),
+        }
+    }
+
+    let path = path.expect("No input file path provided");
     let file = File::open(path).expect("Cannot open file");
     let reader = BufReader::new(file);
 
-    let mut sum = 0;
+    let operators: &[&dyn AnyOperator] = &[&Add, &Mul, &concat];
+    let mut out = std::io::stdout().lock();
+
+    if compare {
+        let (left_to_right, precedence) = compare_evaluations(reader, operators, &mut out);
+        println!("Sum (left to right): {left_to_right}");
+        println!("Sum (precedence): {precedence}");
+    } else {
+        let sum = process(reader, operators, evaluation, mode, &mut out);
+        println!("Sum: {}", sum);
+    }
+}
+
+/// Which satisfying operator sequences are printed for each equation.
+#[derive(Clone, Copy)]
+enum Mode {
+    First,
+    All,
+    Count,
+}
+
+/// How an equation is evaluated: strictly left to right as in the puzzle, or
+/// with operators of higher precedence binding tighter.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Evaluation {
+    LeftToRight,
+    Precedence,
+}
+
+fn process(
+    reader: impl BufRead,
+    operators: &[&dyn AnyOperator],
+    evaluation: Evaluation,
+    mode: Mode,
+    out: &mut impl Write,
+) -> BigUint {
+    let registry = Registry::new(operators);
+    let mut sum = BigUint::default();
 
     for line in reader.lines().map(|line| line.expect("Cannot read line")) {
-        let (test, nums) = line.split_once(':').expect("Invalid input");
+        let (test, nums) = parse(&line);
+
+        if registry.calibrate(test, &nums, evaluation, mode, &line, out) {
+            sum = sum.add(&test.parse().expect("Invalid number"));
+        }
+    }
+
+    sum
+}
+
+/// Calibrates every equation under both evaluations, printing those that only
+/// one of them can calibrate, and returns both sums.
+fn compare_evaluations(
+    reader: impl BufRead,
+    operators: &[&dyn AnyOperator],
+    out: &mut impl Write,
+) -> (BigUint, BigUint) {
+    let registry = Registry::new(operators);
+    let mut left_to_right = BigUint::default();
+    let mut precedence = BigUint::default();
+
+    for line in reader.lines().map(|line| line.expect("Cannot read line")) {
+        let (test, nums) = parse(&line);
+
+        let solutions = [
+            (Evaluation::LeftToRight, "left to right", &mut left_to_right),
+            (Evaluation::Precedence, "precedence", &mut precedence),
+        ]
+        .map(|(evaluation, name, sum)| {
+            let mut solution = Vec::new();
+            let calibrated =
+                registry.calibrate(test, &nums, evaluation, Mode::First, &line, &mut solution);
+            if calibrated {
+                *sum = sum.add(&test.parse().expect("Invalid number"));
+            }
+            (name, solution)
+        });
+
+        if solutions[0].1.is_empty() != solutions[1].1.is_empty() {
+            let (name, solution) = solutions
+                .iter()
+                .find(|(_, solution)| !solution.is_empty())
+                .expect("One evaluation calibrates");
+            write!(out, "{name} only: ").expect("Cannot write");
+            out.write_all(solution).expect("Cannot write");
+        }
+    }
+
+    (left_to_right, precedence)
+}
+
+fn parse(line: &str) -> (&str, Vec<u64>) {
+    let (test, nums) = line.split_once(':').expect("Invalid input");
+
+    let nums = nums
+        .trim()
+        .split(' ')
+        .map(|x| x.parse().expect("Invalid number"))
+        .collect();
+
+    (test, nums)
+}
+
+/// The registered operators, viewed both on machine words and big integers.
+struct Registry<'a> {
+    small: Vec<&'a dyn Operator<u64>>,
+    big: Vec<&'a dyn Operator<BigUint>>,
+}
+
+impl<'a> Registry<'a> {
+    fn new(operators: &[&'a dyn AnyOperator]) -> Self {
+        Self {
+            small: operators
+                .iter()
+                .map(|operator| *operator as &dyn Operator<u64>)
+                .collect(),
+            big: operators
+                .iter()
+                .map(|operator| *operator as &dyn Operator<BigUint>)
+                .collect(),
+        }
+    }
+
+    fn calibrate(
+        &self,
+        test: &str,
+        nums: &[u64],
+        evaluation: Evaluation,
+        mode: Mode,
+        line: &str,
+        out: &mut impl Write,
+    ) -> bool {
+        // Only test values or intermediate results too large for a machine
+        // word take the slow path
+        let small = test
+            .parse::<u64>()
+            .ok()
+            .and_then(|test| calibrate(test, nums, &self.small, evaluation, mode, line, out).ok());
+
+        small.unwrap_or_else(|| {
+            let test = test.parse().expect("Invalid number");
+            calibrate::<BigUint>(test, nums, &self.big, evaluation, mode, line, out)
+                .expect("Big integers never overflow")
+        })
+    }
+}
+
+fn calibrate<N: Num>(
+    test: N,
+    nums: &[u64],
+    operators: &[&dyn Operator<N>],
+    evaluation: Evaluation,
+    mode: Mode,
+    line: &str,
+    out: &mut impl Write,
+) -> Result<bool, Overflow> {
+    let calibrated = match mode {
+        Mode::First => match solve(&test, nums, operators, evaluation)? {
+            Some(solution) => {
+                writeln!(out, "{}", display(&test, nums, &solution)).expect("Cannot write");
+                true
+            }
+            None => false,
+        },
+        Mode::All => {
+            let solutions = solutions(&test, nums, operators, evaluation)?;
+            for solution in solutions.iter() {
+                writeln!(out, "{}", display(&test, nums, solution)).expect("Cannot write");
+            }
+            !solutions.is_empty()
+        }
+        Mode::Count => match count(&test, nums, operators, evaluation)? {
+            0 => false,
+            count => {
+                writeln!(out, "{line} (solutions: {count})").expect("Cannot write");
+                true
+            }
+        },
+    };
+
+    Ok(calibrated)
+}
+
+/// A branch of the search overflowed `N` while it could still have reached
+/// the test value, so the equation has to be calibrated with big integers.
+#[derive(Debug, PartialEq, Eq)]
+struct Overflow;
+
+/// Returns an operator sequence that turns `nums` into `test`, the first one
+/// the search comes across.
+fn solve<'a, N: Num>(
+    test: &N,
+    nums: &[u64],
+    operators: &[&'a dyn Operator<N>],
+    evaluation: Evaluation,
+) -> Result<Option<Vec<&'a dyn Operator<N>>>, Overflow> {
+    let mut found = None;
+    search(test, nums, operators, evaluation, |solution| {
+        found = Some(solution.to_vec());
+        ControlFlow::Break(())
+    })?;
+    debug_assert!(found
+        .as_ref()
+        .is_none_or(|solution| evaluate(nums, solution, evaluation).as_ref() == Some(test)));
+    Ok(found)
+}
+
+fn solutions<'a, N: Num>(
+    test: &N,
+    nums: &[u64],
+    operators: &[&'a dyn Operator<N>],
+    evaluation: Evaluation,
+) -> Result<Vec<Vec<&'a dyn Operator<N>>>, Overflow> {
+    let mut found = Vec::new();
+    search(test, nums, operators, evaluation, |solution| {
+        found.push(solution.to_vec());
+        ControlFlow::Continue(())
+    })?;
+    Ok(found)
+}
+
+fn count<N: Num>(
+    test: &N,
+    nums: &[u64],
+    operators: &[&dyn Operator<N>],
+    evaluation: Evaluation,
+) -> Result<usize, Overflow> {
+    let mut count = 0;
+    search(test, nums, operators, evaluation, |_| {
+        count += 1;
+        ControlFlow::Continue(())
+    })?;
+    Ok(count)
+}
+
+/// Passes every operator sequence that turns `nums` into `test` to `visit`,
+/// until it breaks. Gives up if a branch that might still reach `test`
+/// overflows `N`.
+fn search<'a, N: Num>(
+    test: &N,
+    nums: &[u64],
+    operators: &[&'a dyn Operator<N>],
+    evaluation: Evaluation,
+    visit: impl FnMut(&[&'a dyn Operator<N>]) -> ControlFlow<()>,
+) -> Result<(), Overflow> {
+    match evaluation {
+        Evaluation::LeftToRight => search_backwards(test, nums, operators, visit),
+        Evaluation::Precedence => search_precedence(test, nums, operators, visit),
+    }
+}
 
-        let test = test.parse().expect("Invalid number");
-        let nums = nums.trim().split(' ').collect();
+/// Works backwards from the test value, undoing the rightmost operator first,
+/// so a branch is dropped as soon as the remaining value cannot be produced.
+fn search_backwards<'a, N: Num>(
+    test: &N,
+    nums: &[u64],
+    operators: &[&'a dyn Operator<N>],
+    mut visit: impl FnMut(&[&'a dyn Operator<N>]) -> ControlFlow<()>,
+) -> Result<(), Overflow> {
+    fn search<'a, N: Num>(
+        test: &N,
+        nums: &[u64],
+        operators: &[&'a dyn Operator<N>],
+        solution: &mut [&'a dyn Operator<N>],
+        visit: &mut impl FnMut(&[&'a dyn Operator<N>]) -> ControlFlow<()>,
+        overflowed: &mut bool,
+    ) -> ControlFlow<()> {
+        let (last, rest) = match nums {
+            [] => return ControlFlow::Continue(()),
+            [first] if N::from_u64(*first) == *test => return visit(solution),
+            [_] => return ControlFlow::Continue(()),
+            [rest @ .., last] => (*last, rest),
+        };
 
-        if solve(test, &nums) {
-            sum += test;
+        for operator in operators {
+            solution[rest.len() - 1] = *operator;
+            if operator.absorbs(test, last) {
+                let (first, rest) = rest.split_first().expect("No operands left");
+                let first = N::from_u64(*first);
+                search_any(first, rest, 0, operators, solution, visit, overflowed)?;
+            } else if let Some(test) = operator.undo(test, last) {
+                search(&test, rest, operators, solution, visit, overflowed)?;
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    /// Tries every operator sequence on the operands left, as whatever they
+    /// evaluate to will do, stopping if one overflows `N`. `value` is what
+    /// the operands before `nums` evaluated to, and `index` is where the next
+    /// operator goes in `solution`.
+    fn search_any<'a, N: Num>(
+        value: N,
+        nums: &[u64],
+        index: usize,
+        operators: &[&'a dyn Operator<N>],
+        solution: &mut [&'a dyn Operator<N>],
+        visit: &mut impl FnMut(&[&'a dyn Operator<N>]) -> ControlFlow<()>,
+        overflowed: &mut bool,
+    ) -> ControlFlow<()> {
+        let Some((next, nums)) = nums.split_first() else {
+            return visit(solution);
+        };
+
+        for operator in operators {
+            // The zero after these operands absorbs any value, however large
+            let Some(value) = operator.apply(&value, &N::from_u64(*next)) else {
+                *overflowed = true;
+                return ControlFlow::Break(());
+            };
+            solution[index] = *operator;
+            search_any(
+                value,
+                nums,
+                index + 1,
+                operators,
+                solution,
+                visit,
+                overflowed,
+            )?;
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    let Some(first) = operators.first() else {
+        return Ok(());
+    };
+
+    let mut solution = vec![*first; nums.len().saturating_sub(1)];
+    let mut overflowed = false;
+    let _ = search(
+        test,
+        nums,
+        operators,
+        &mut solution,
+        &mut visit,
+        &mut overflowed,
+    );
+
+    match overflowed {
+        true => Err(Overflow),
+        false => Ok(()),
+    }
+}
+
+/// Works forwards, evaluating with precedence as operators are chosen: an
+/// operand stays pending until an operator binding no tighter follows it.
+///
+/// Every operator only grows its positive operands, so when no operand is
+/// zero any value above the test value can never come back down, and the
+/// branch is dropped. The same goes for values that overflow `N`.
+fn search_precedence<'a, N: Num>(
+    test: &N,
+    nums: &[u64],
+    operators: &[&'a dyn Operator<N>],
+    mut visit: impl FnMut(&[&'a dyn Operator<N>]) -> ControlFlow<()>,
+) -> Result<(), Overflow> {
+    struct Search<'a, 'b, N, F> {
+        test: &'b N,
+        operators: &'b [&'a dyn Operator<N>],
+        prune: bool,
+        overflowed: bool,
+        solution: Vec<&'a dyn Operator<N>>,
+        visit: F,
+    }
+
+    impl<'a, N: Num, F: FnMut(&[&'a dyn Operator<N>]) -> ControlFlow<()>> Search<'a, '_, N, F> {
+        /// Drops a branch that overflowed, unless a zero could still bring
+        /// its value back down, in which case the whole search stops.
+        fn overflow(&mut self) -> ControlFlow<()> {
+            if self.prune {
+                return ControlFlow::Continue(());
+            }
+            self.overflowed = true;
+            ControlFlow::Break(())
+        }
+
+        fn search(
+            &mut self,
+            nums: &[u64],
+            pending: &[(N, &'a dyn Operator<N>)],
+            current: N,
+        ) -> ControlFlow<()> {
+            if self.prune && current > *self.test {
+                return ControlFlow::Continue(());
+            }
+
+            let Some((next, rest)) = nums.split_first() else {
+                let mut pending = pending.to_vec();
+                return match reduce(&mut pending, current, 0) {
+                    Some(result) if result == *self.test => (self.visit)(&self.solution),
+                    Some(_) => ControlFlow::Continue(()),
+                    None => self.overflow(),
+                };
+            };
+
+            for operator in self.operators {
+                let mut pending = pending.to_vec();
+                let Some(lhs) = reduce(&mut pending, current.clone(), operator.precedence()) else {
+                    self.overflow()?;
+                    continue;
+                };
+
+                if self.prune && lhs > *self.test {
+                    continue;
+                }
+
+                pending.push((lhs, *operator));
+                self.solution.push(*operator);
+                let flow = self.search(rest, &pending, N::from_u64(*next));
+                self.solution.pop();
+                flow?;
+            }
+
+            ControlFlow::Continue(())
         }
     }
 
-    println!("Sum: {}", sum);
+    let Some((first, rest)) = nums.split_first() else {
+        return Ok(());
+    };
+
+    let mut search = Search {
+        test,
+        operators,
+        prune: !nums.contains(&0),
+        overflowed: false,
+        solution: Vec::with_capacity(rest.len()),
+        visit: &mut visit,
+    };
+    let _ = search.search(rest, &[], N::from_u64(*first));
+
+    match search.overflowed {
+        true => Err(Overflow),
+        false => Ok(()),
+    }
 }
 
-fn solve(test: usize, nums: &Vec<&str>) -> bool {
-    fn solve(test: usize, nums: &[&str], idx: usize, running: usize) -> bool {
-        if idx == nums.len() {
-            return running == test;
+/// Combines `current` with pending operands while their operators bind at
+/// least as tightly as `precedence`, so equal precedence associates to the
+/// left. Returns `None` on overflow.
+fn reduce<N: Num>(
+    pending: &mut Vec<(N, &dyn Operator<N>)>,
+    mut current: N,
+    precedence: u8,
+) -> Option<N> {
+    while let Some((lhs, operator)) = pending.last() {
+        if operator.precedence() < precedence {
+            break;
         }
+        current = operator.apply(lhs, &current)?;
+        pending.pop();
+    }
+    Some(current)
+}
+
+/// Evaluates an operator sequence, or `None` if an intermediate value
+/// overflows `N`.
+fn evaluate<N: Num>(
+    nums: &[u64],
+    operators: &[&dyn Operator<N>],
+    evaluation: Evaluation,
+) -> Option<N> {
+    let mut pending = Vec::new();
+    let mut current = N::from_u64(nums[0]);
+
+    for (operator, rhs) in operators.iter().zip(&nums[1..]) {
+        current = match evaluation {
+            Evaluation::LeftToRight => operator.apply(&current, &N::from_u64(*rhs))?,
+            Evaluation::Precedence => {
+                let lhs = reduce(&mut pending, current, operator.precedence())?;
+                pending.push((lhs, *operator));
+                N::from_u64(*rhs)
+            }
+        };
+    }
+
+    reduce(&mut pending, current, 0)
+}
+
+/// Formats a calibrated equation as e.g. `3267 = 81 + 40 * 27`.
+fn display<N: Num>(test: &N, nums: &[u64], operators: &[&dyn Operator<N>]) -> String {
+    let mut display = format!("{test} = {}", nums[0]);
+    for (operator, num) in operators.iter().zip(&nums[1..]) {
+        display.push_str(&format!(" {} {num}", operator.symbol()));
+    }
+    display
+}
+
+/// Unsigned integers the solver works in. Equation operands always fit in a
+/// `u64`; only test values and intermediate results may need to be wider.
+trait Num: Clone + Ord + Display {
+    fn from_u64(value: u64) -> Self;
+
+    /// Number of decimal digits, counting zero as a single digit.
+    fn digits(&self) -> u32;
+
+    fn pow10(exp: u32) -> Option<Self>;
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self>;
+
+    fn checked_sub(&self, rhs: u64) -> Option<Self>;
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+
+    fn div_rem(&self, rhs: u64) -> (Self, u64);
+}
+
+impl Num for u64 {
+    fn from_u64(value: u64) -> Self {
+        value
+    }
+
+    fn digits(&self) -> u32 {
+        self.checked_ilog10().unwrap_or(0) + 1
+    }
+
+    fn pow10(exp: u32) -> Option<Self> {
+        10u64.checked_pow(exp)
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        u64::checked_add(*self, *rhs)
+    }
+
+    fn checked_sub(&self, rhs: u64) -> Option<Self> {
+        u64::checked_sub(*self, rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        u64::checked_mul(*self, *rhs)
+    }
+
+    fn div_rem(&self, rhs: u64) -> (Self, u64) {
+        (self / rhs, self % rhs)
+    }
+}
+
+impl Num for BigUint {
+    fn from_u64(value: u64) -> Self {
+        BigUint::from_u64(value)
+    }
+
+    fn digits(&self) -> u32 {
+        BigUint::digits(self)
+    }
+
+    fn pow10(exp: u32) -> Option<Self> {
+        Some(BigUint::pow10(exp))
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        Some(self.add(rhs))
+    }
+
+    fn checked_sub(&self, rhs: u64) -> Option<Self> {
+        BigUint::checked_sub(self, &BigUint::from_u64(rhs))
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self.mul(rhs))
+    }
+
+    fn div_rem(&self, rhs: u64) -> (Self, u64) {
+        BigUint::div_rem(self, rhs)
+    }
+}
+
+/// A binary operator that can be reversed: given `result = lhs op rhs` and
+/// `rhs`, recovers `lhs`, or `None` if no such `lhs` exists.
+///
+/// Both directions are checked: `apply` fails rather than overflowing, and
+/// `undo` fails as soon as `rhs` alone would exceed `result`, which prunes the
+/// branch.
+trait Operator<N> {
+    fn symbol(&self) -> &'static str;
+
+    /// How tightly the operator binds when evaluating with precedence; higher
+    /// binds tighter.
+    fn precedence(&self) -> u8;
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N>;
+
+    fn undo(&self, result: &N, rhs: u64) -> Option<N>;
+
+    /// Whether every `lhs` gives `result` with this `rhs`, as multiplying by
+    /// zero does, so there is nothing for `undo` to recover.
+    fn absorbs(&self, _result: &N, _rhs: u64) -> bool {
+        false
+    }
+}
+
+/// An operator usable on both machine words and big integers, so the same
+/// registry serves every equation regardless of its size.
+trait AnyOperator: Operator<u64> + Operator<BigUint> {}
+
+impl<T: Operator<u64> + Operator<BigUint>> AnyOperator for T {}
+
+struct Add;
+
+impl<N: Num> Operator<N> for Add {
+    fn symbol(&self) -> &'static str {
+        "+"
+    }
+
+    fn precedence(&self) -> u8 {
+        1
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.checked_add(rhs)
+    }
+
+    fn undo(&self, result: &N, rhs: u64) -> Option<N> {
+        result.checked_sub(rhs)
+    }
+}
+
+struct Mul;
+
+impl<N: Num> Operator<N> for Mul {
+    fn symbol(&self) -> &'static str {
+        "*"
+    }
+
+    fn precedence(&self) -> u8 {
+        2
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.checked_mul(rhs)
+    }
+
+    fn absorbs(&self, result: &N, rhs: u64) -> bool {
+        rhs == 0 && *result == N::from_u64(0)
+    }
+
+    fn undo(&self, result: &N, rhs: u64) -> Option<N> {
+        // A zero `rhs` gives zero for any `lhs`, which `absorbs` covers
+        if rhs == 0 {
+            return None;
+        }
+        match result.div_rem(rhs) {
+            (lhs, 0) => Some(lhs),
+            _ => None,
+        }
+    }
+}
+
+struct Concat {
+    precedence: u8,
+}
+
+impl Default for Concat {
+    /// Binds tighter than `*`, like the digits of a single number.
+    fn default() -> Self {
+        Self { precedence: 3 }
+    }
+}
+
+impl<N: Num> Operator<N> for Concat {
+    fn symbol(&self) -> &'static str {
+        "||"
+    }
+
+    fn precedence(&self) -> u8 {
+        self.precedence
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.checked_mul(&N::pow10(rhs.digits())?)?.checked_add(rhs)
+    }
+
+    fn undo(&self, result: &N, rhs: u64) -> Option<N> {
+        // Split off the low digits in two steps, since `10^digits` itself
+        // overflows a `u64` when `rhs` has twenty digits
+        let low = 10u64.pow(rhs.digits() - 1);
+        let (high, low_digits) = result.div_rem(low);
+        let (lhs, top_digit) = high.div_rem(10);
+        (top_digit as u128 * low as u128 + low_digits as u128 == rhs as u128).then_some(lhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r"
+        190: 10 19
+        3267: 81 40 27
+        83: 17 5
+        156: 15 6
+        7290: 6 8 6 15
+        161011: 16 10 13
+        192: 17 8 14
+        21037: 9 7 18 13
+        292: 11 6 16 20
+    ";
+
+    fn example() -> String {
+        EXAMPLE
+            .trim()
+            .lines()
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test() {
+        let input = example();
+        let mut out = Vec::new();
+
+        let sum = process(
+            input.as_bytes(),
+            &[&Add, &Mul],
+            Evaluation::LeftToRight,
+            Mode::First,
+            &mut out,
+        );
+
+        assert_eq!(sum, BigUint::from_u64(3749));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "190 = 10 * 19\n3267 = 81 * 40 + 27\n292 = 11 + 6 * 16 + 20\n"
+        );
+
+        let sum = process(
+            input.as_bytes(),
+            &[&Add, &Mul, &Concat::default()],
+            Evaluation::LeftToRight,
+            Mode::First,
+            &mut Vec::new(),
+        );
+
+        assert_eq!(sum, BigUint::from_u64(11387));
+    }
+
+    #[test]
+    fn test_solutions() {
+        let operators: &[&dyn Operator<u64>] = &[&Add, &Mul];
+
+        let solutions = solutions(&3267, &[81, 40, 27], operators, Evaluation::LeftToRight)
+            .unwrap()
+            .iter()
+            .map(|solution| display(&3267, &[81, 40, 27], solution))
+            .collect::<Vec<_>>();
+
+        assert_eq!(solutions, ["3267 = 81 * 40 + 27", "3267 = 81 + 40 * 27"]);
+        assert_eq!(
+            count(&3267, &[81, 40, 27], operators, Evaluation::LeftToRight),
+            Ok(2)
+        );
+        assert_eq!(
+            count(&83, &[17, 5], operators, Evaluation::LeftToRight),
+            Ok(0)
+        );
+        assert!(solve(&83, &[17, 5], operators, Evaluation::LeftToRight)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_zero_operand() {
+        let operators: &[&dyn Operator<u64>] = &[&Add, &Mul];
+
+        let solution = solve(&0, &[5, 0], operators, Evaluation::LeftToRight)
+            .unwrap()
+            .unwrap();
+        assert_eq!(display(&0, &[5, 0], &solution), "0 = 5 * 0");
+
+        let solutions = solutions(&0, &[3, 4, 0], operators, Evaluation::LeftToRight)
+            .unwrap()
+            .iter()
+            .map(|solution| display(&0, &[3, 4, 0], solution))
+            .collect::<Vec<_>>();
+        assert_eq!(solutions, ["0 = 3 + 4 * 0", "0 = 3 * 4 * 0"]);
+
+        // Values before the zero that overflow a machine word count too, once
+        // calibrated with big integers
+        let nums = [u64::MAX, 1, 0];
+        assert_eq!(
+            count(&0, &nums, operators, Evaluation::LeftToRight),
+            Err(Overflow)
+        );
+        assert_eq!(
+            count(&0, &nums, operators, Evaluation::Precedence),
+            Err(Overflow)
+        );
+
+        let input = "0: 18446744073709551615 1 0\n0: 18446744073709551615 2 0";
+        for (evaluation, count) in [(Evaluation::LeftToRight, 2), (Evaluation::Precedence, 1)] {
+            let mut out = Vec::new();
+            process(
+                input.as_bytes(),
+                &[&Add, &Mul],
+                evaluation,
+                Mode::Count,
+                &mut out,
+            );
+
+            let expected = input
+                .lines()
+                .map(|line| format!("{line} (solutions: {count})\n"))
+                .collect::<String>();
+            assert_eq!(String::from_utf8(out).unwrap(), expected);
+        }
+
+        assert_eq!(
+            count(&6, &[5, 0, 6], operators, Evaluation::LeftToRight),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn test_concat() {
+        let operators: &[&dyn Operator<u64>] = &[&Add, &Mul, &Concat::default()];
+        let solution = solve(&7290, &[6, 8, 6, 15], operators, Evaluation::LeftToRight)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            display(&7290, &[6, 8, 6, 15], &solution),
+            "7290 = 6 * 8 || 6 * 15"
+        );
+        assert_eq!(Concat::default().undo(&156u64, 6), Some(15));
+        assert_eq!(Concat::default().undo(&156u64, 5), None);
+        assert_eq!(Concat::default().undo(&1000u64, 0), Some(100));
+        assert_eq!(Concat::default().undo(&u64::MAX, u64::MAX), Some(0));
+        assert_eq!(Concat::default().apply(&2u64, &u64::MAX), None);
+    }
+
+    #[test]
+    fn test_overflow() {
+        // Wrapping arithmetic would give 2^63 * 2 + 4 = 4
+        let input = "4: 9223372036854775808 2 4\n36893488147419103230: 18446744073709551615 2";
+        let mut out = Vec::new();
+
+        let sum = process(
+            input.as_bytes(),
+            &[&Add, &Mul, &Concat::default()],
+            Evaluation::LeftToRight,
+            Mode::All,
+            &mut out,
+        );
+
+        assert_eq!(sum.to_string(), "36893488147419103230");
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "36893488147419103230 = 18446744073709551615 * 2\n"
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        let operators: &[&dyn Operator<u64>] = &[&Add, &Mul];
+
+        assert_eq!(
+            evaluate(&[2, 3, 4], &[&Add, &Mul], Evaluation::LeftToRight),
+            Some(20)
+        );
+        assert_eq!(
+            evaluate(&[2, 3, 4], &[&Add, &Mul], Evaluation::Precedence),
+            Some(14)
+        );
+        assert_eq!(
+            evaluate(&[2, 3, 4], &[&Mul, &Add], Evaluation::Precedence),
+            Some(10)
+        );
+
+        let solutions = solutions(&3267, &[81, 40, 27], operators, Evaluation::Precedence)
+            .unwrap()
+            .iter()
+            .map(|solution| display(&3267, &[81, 40, 27], solution))
+            .collect::<Vec<_>>();
+
+        assert_eq!(solutions, ["3267 = 81 * 40 + 27"]);
+        assert_eq!(
+            count(&1161, &[81, 40, 27], operators, Evaluation::Precedence),
+            Ok(1)
+        );
+        assert_eq!(
+            count(&1161, &[81, 40, 27], operators, Evaluation::LeftToRight),
+            Ok(0)
+        );
+        assert_eq!(
+            count(&0, &[5, 0, 7, 0], operators, Evaluation::Precedence),
+            Ok(3)
+        );
+    }
+
+    #[test]
+    fn test_concat_precedence() {
+        let tight = Concat::default();
+        let loose = Concat { precedence: 1 };
+
+        assert_eq!(
+            evaluate(&[1, 2, 3], &[&Add, &tight], Evaluation::Precedence),
+            Some(24)
+        );
+        assert_eq!(
+            evaluate(&[1, 2, 3], &[&Add, &loose], Evaluation::Precedence),
+            Some(33)
+        );
+        assert_eq!(
+            evaluate(&[2, 3, 4], &[&loose, &Mul], Evaluation::Precedence),
+            Some(212)
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let input = "1161: 81 40 27\n3267: 81 40 27\n20: 2 3 4";
+        let mut out = Vec::new();
+
+        let (left_to_right, precedence) =
+            compare_evaluations(input.as_bytes(), &[&Add, &Mul], &mut out);
+
+        assert_eq!(left_to_right.to_string(), "3287");
+        assert_eq!(precedence.to_string(), "4428");
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "precedence only: 1161 = 81 + 40 * 27\nleft to right only: 20 = 2 + 3 * 4\n"
+        );
+    }
+
+    #[test]
+    fn test_big() {
+        let a = "123456789012345678901234567890".parse::<BigUint>().unwrap();
+        let b = BigUint::from_u64(987654321);
 
-        let curr = nums[idx];
-        let num: usize = curr.parse().expect("Invalid number");
-        let idx = idx + 1;
+        assert_eq!(a.to_string(), "123456789012345678901234567890");
+        assert_eq!(a.digits(), 30);
+        assert_eq!(
+            a.mul(&b).to_string(),
+            "121932631124828532112482853211126352690"
+        );
+        assert_eq!(a.add(&b).to_string(), "123456789012345678902222222211");
+        assert_eq!(
+            a.checked_sub(&b).unwrap().to_string(),
+            "123456789012345678900246913569"
+        );
+        assert_eq!(b.checked_sub(&a), None);
 
-        solve(test, nums, idx, running + num)
-            || solve(test, nums, idx, running * num)
-            || solve(test, nums, idx, {
-                let mut running = running.to_string();
-                running.push_str(curr);
-                running.parse().expect("Invalid number")
-            })
+        let (quotient, remainder) = a.div_rem(1_000_000_007);
+        assert_eq!(quotient.to_string(), "123456788148148161864");
+        assert_eq!(remainder, 197434842);
     }
-    solve(test, nums, 0, 0)
 }
@@ -1,30 +1,98 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::File,
-    io::{BufReader, Read},
+    io::{BufRead, BufReader},
 };
 
 fn main() {
-    let path = std::env::args()
-        .nth(1)
-        .expect("No input file path provided");
+    let mut path = None;
+    let mut rule = None;
+    let mut min = None;
+    let mut max = None;
+    let mut reduced = false;
+    let mut render = false;
+    let mut color = true;
+    let mut topology = None;
+    let mut window = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rule" => rule = Some(args.next().expect("No rule provided")),
+            "--min" => min = Some(args.next().expect("No minimum provided")),
+            "--max" => max = Some(args.next().expect("No maximum provided")),
+            "--gcd" => reduced = true,
+            "--render" => render = true,
+            "--no-color" => color = false,
+            "--topology" => topology = Some(args.next().expect("No topology provided")),
+            "--window" => {
+                let spec = args.next().expect("No window provided");
+                window = Some(spec.parse::<Window>().expect("Cannot parse window"));
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("Unexpected argument: {arg}"),
+        }
+    }
+
+    let rule = match rule.as_deref() {
+        None | Some("harmonics") => Rule::Harmonics {
+            min: min.map_or(0, |min| min.parse().expect("Cannot parse minimum")),
+            max: max.map(|max| max.parse().expect("Cannot parse maximum")),
+            reduced,
+        },
+        Some("exact") => Rule::Exact,
+        Some("segment") => Rule::Segment,
+        Some("collinear") => Rule::COLLINEAR,
+        Some(rule) => panic!("Unknown rule: {rule}"),
+    };
+
+    let path = path.expect("No input file path provided");
     let file = File::open(path).expect("Cannot open file");
     let reader = BufReader::new(file);
 
-    let mut locations: [_; TOTAL_LEN] = std::array::from_fn(|_| Vec::new());
+    let (mut grid, locations) = parse(reader);
+
+    grid.topology = match topology.as_deref() {
+        None | Some("bounded") => Topology::Bounded,
+        Some("toroidal") => Topology::Toroidal,
+        Some("unbounded") => Topology::Unbounded(window.unwrap_or_else(|| grid.window())),
+        Some(topology) => panic!("Unknown topology: {topology}"),
+    };
+
+    let by_frequency = antinodes_by_frequency(&grid, &locations, rule);
+
+    if render {
+        print!("{}", self::render(&grid, &locations, &by_frequency, color));
+        println!();
+        print!("{}", table(&locations, &by_frequency));
+        println!();
+    }
+
+    let satellites = antinodes(&by_frequency).len();
+
+    println!("Satellites: {}", satellites);
+}
+
+fn parse(mut reader: impl BufRead) -> (Grid, Frequencies) {
+    let mut input = String::new();
+    reader
+        .read_to_string(&mut input)
+        .expect("Cannot read input as UTF-8");
+
+    let mut locations = Frequencies::default();
     let mut offset = 0;
     let mut columns = None;
     let mut rows = 0;
     let mut exited_newline = false;
 
-    for byte in reader.bytes().map(|byte| byte.expect("Cannot read byte")) {
+    for char in input.chars() {
         exited_newline = false;
 
-        if byte == b'\r' {
+        if char == '\r' {
             continue;
         }
 
-        if byte == b'\n' {
+        if char == '\n' {
             exited_newline = true;
             let test = columns.get_or_insert(offset);
             assert_eq!(offset % *test, 0);
@@ -32,13 +100,12 @@ fn main() {
             continue;
         }
 
-        if byte == b'.' {
+        if char == '.' {
             offset += 1;
             continue;
         }
 
-        let idx = index(byte);
-        locations[idx].push(offset);
+        locations.push(char, offset);
 
         offset += 1;
     }
@@ -48,104 +115,386 @@ fn main() {
     }
 
     let columns = columns.expect("No columns found");
-    let grid = Grid { columns, rows };
-    let mut satellites = HashSet::new();
+    let grid = Grid {
+        columns,
+        rows,
+        topology: Topology::Bounded,
+    };
+    (grid, locations)
+}
+
+/// Every in-bounds antinode produced by any pair of same-frequency antennas,
+/// from the antinodes of each frequency.
+fn antinodes(by_frequency: &[(char, HashSet<usize>)]) -> HashSet<usize> {
+    by_frequency
+        .iter()
+        .flat_map(|(_, antinodes)| antinodes.iter().copied())
+        .collect()
+}
+
+/// The antinodes each frequency produces on its own, for every frequency
+/// with at least two antennas.
+fn antinodes_by_frequency(
+    grid: &Grid,
+    locations: &Frequencies,
+    rule: Rule,
+) -> Vec<(char, HashSet<usize>)> {
+    let mut by_frequency = Vec::new();
+
+    for (frequency, location) in locations.iter().filter(|(_, location)| location.len() > 1) {
+        let mut antinodes = HashSet::new();
+
+        for (i, first) in location.iter().enumerate() {
+            for second in &location[i + 1..] {
+                let first = grid.offset_to_point(*first).expect("Invalid offset");
+                let second = grid.offset_to_point(*second).expect("Invalid offset");
+                rule.apply(grid, first, second, |offset| {
+                    antinodes.insert(offset);
+                });
+            }
+        }
 
-    for location in locations.iter().filter(|location| location.len() > 1) {
-        location
+        by_frequency.push((frequency, antinodes));
+    }
+
+    by_frequency
+}
+
+/// Draws the grid's window with antinodes as `#`, as in the puzzle statement.
+/// Antennas take priority over antinodes, and an antinode produced by several
+/// frequencies takes the colour of the first.
+fn render(
+    grid: &Grid,
+    locations: &Frequencies,
+    by_frequency: &[(char, HashSet<usize>)],
+    color: bool,
+) -> String {
+    const COLORS: [u8; 12] = [9, 10, 11, 12, 13, 14, 208, 118, 199, 45, 226, 141];
+
+    let colors = by_frequency
+        .iter()
+        .enumerate()
+        .map(|(i, (frequency, _))| (*frequency, COLORS[i % COLORS.len()]))
+        .collect::<HashMap<_, _>>();
+
+    let window = grid.window();
+    let mut cells = vec![('.', None); window.columns * window.rows];
+
+    for (frequency, antinodes) in by_frequency.iter().rev() {
+        for offset in antinodes {
+            cells[*offset] = ('#', colors.get(frequency));
+        }
+    }
+
+    for (frequency, location) in locations.iter() {
+        let offsets = location
             .iter()
-            .flat_map(|x| location.iter().map(|y| (*x, *y)))
-            .filter_map(|(x, y)| (x != y).then(|| grid.inverted_distanced_points(x, y)))
-            .flatten()
-            .for_each(|offset| {
-                satellites.insert(offset);
-            });
+            .filter_map(|offset| grid.offset_to_point(*offset))
+            .filter_map(|point| grid.point_to_offset(point));
+
+        for offset in offsets {
+            cells[offset] = (frequency, colors.get(&frequency));
+        }
     }
 
-    println!("Satellites: {}", satellites.len());
+    let mut out = String::new();
+    for row in cells.chunks(window.columns) {
+        for (char, code) in row {
+            match code {
+                Some(code) if color => out.push_str(&format!("\x1b[38;5;{code}m{char}\x1b[0m")),
+                _ => out.push(*char),
+            }
+        }
+        out.push('\n');
+    }
+    out
 }
 
-struct Grid {
-    columns: usize,
-    rows: usize,
+/// Per-frequency antenna and antinode counts, before overlapping antinodes
+/// from different frequencies are merged.
+fn table(locations: &Frequencies, by_frequency: &[(char, HashSet<usize>)]) -> String {
+    let antinodes = by_frequency
+        .iter()
+        .map(|(frequency, antinodes)| (*frequency, antinodes.len()))
+        .collect::<HashMap<_, _>>();
+
+    let mut out = String::from("Frequency  Antennas  Antinodes\n");
+    for (frequency, location) in locations
+        .iter()
+        .filter(|(_, location)| !location.is_empty())
+    {
+        let count = antinodes.get(&frequency).copied().unwrap_or(0);
+        out.push_str(&format!(
+            "{frequency:<9}  {:>8}  {count:>9}\n",
+            location.len()
+        ));
+    }
+    out
 }
 
-impl Grid {
-    fn offset_to_point(&self, offset: usize) -> Option<(usize, usize)> {
-        match offset {
-            offset if offset < self.columns * self.rows => {
-                Some((offset % self.columns, offset / self.columns))
-            }
-            _ => None,
+/// Antenna offsets grouped by frequency. The puzzle's digits and ASCII
+/// letters go in a fixed table, anything else in a map.
+struct Frequencies {
+    ascii: [Vec<usize>; TOTAL_LEN],
+    other: HashMap<char, Vec<usize>>,
+}
+
+impl Default for Frequencies {
+    fn default() -> Self {
+        Self {
+            ascii: std::array::from_fn(|_| Vec::new()),
+            other: HashMap::new(),
         }
     }
+}
 
-    fn point_to_offset(&self, point: (usize, usize)) -> Option<usize> {
-        match point.1 * self.columns + point.0 {
-            offset if offset < self.columns * self.rows => Some(offset),
-            _ => None,
+impl Frequencies {
+    fn push(&mut self, frequency: char, offset: usize) {
+        let ascii = u8::try_from(frequency).ok().and_then(index);
+        match ascii {
+            Some(idx) => self.ascii[idx].push(offset),
+            None => self.other.entry(frequency).or_default().push(offset),
         }
     }
 
-    fn inverted_distanced_points(
-        &self,
-        first: usize,
-        second: usize,
-    ) -> impl '_ + Iterator<Item = usize> {
-        struct Iter<'a> {
-            curr: Option<usize>,
-            next: usize,
-            grid: &'a Grid,
-        }
-
-        impl Iterator for Iter<'_> {
-            type Item = usize;
-
-            fn next(&mut self) -> Option<Self::Item> {
-                let curr = self.curr?;
-                let next = self.grid.inverted_distanced_point(curr, self.next);
-                self.next = curr;
-                self.curr = next;
-                Some(curr)
+    fn iter(&self) -> impl '_ + Iterator<Item = (char, &[usize])> {
+        let ascii = self
+            .ascii
+            .iter()
+            .enumerate()
+            .map(|(idx, location)| (label(idx), location.as_slice()));
+
+        let mut other = self
+            .other
+            .iter()
+            .map(|(frequency, location)| (*frequency, location.as_slice()))
+            .collect::<Vec<_>>();
+        other.sort_unstable_by_key(|(frequency, _)| *frequency);
+
+        ascii.chain(other)
+    }
+}
+
+/// Where a pair of same-frequency antennas produces antinodes.
+#[derive(Clone, Copy, Debug)]
+enum Rule {
+    /// One antinode beyond each antenna, twice as far from the other one.
+    Exact,
+    /// Antinodes beyond each antenna at every whole multiple of a step from
+    /// `min` to `max`, where the zeroth multiple is the antenna itself.
+    ///
+    /// The step is the pair's separation, as in the puzzle, unless `reduced`
+    /// divides it by the gcd of its components. Then every grid cell on the
+    /// line is reachable, and with `min` at zero the cells between the
+    /// antennas are included as well.
+    Harmonics {
+        min: usize,
+        max: Option<usize>,
+        reduced: bool,
+    },
+    /// The antinodes between the antennas, a third of the way from either
+    /// end, wherever those fall exactly on a grid cell.
+    Segment,
+}
+
+impl Rule {
+    /// Every grid cell lying exactly on the line through both antennas.
+    const COLLINEAR: Self = Self::Harmonics {
+        min: 0,
+        max: None,
+        reduced: true,
+    };
+
+    fn apply(&self, grid: &Grid, first: Point, second: Point, mut emit: impl FnMut(usize)) {
+        let delta = (second.0 - first.0, second.1 - first.1);
+
+        match *self {
+            Rule::Exact => {
+                Rule::Harmonics {
+                    min: 1,
+                    max: Some(1),
+                    reduced: false,
+                }
+                .apply(grid, first, second, emit);
             }
-        }
 
-        Iter {
-            curr: Some(first),
-            next: second,
-            grid: self,
+            Rule::Harmonics { min, max, reduced } => {
+                let gcd = match reduced {
+                    true => gcd(delta.0.unsigned_abs(), delta.1.unsigned_abs()),
+                    false => 1,
+                };
+                let step = (delta.0 / gcd as isize, delta.1 / gcd as isize);
+
+                grid.walk(second, step, min, max, &mut emit);
+                grid.walk(first, (-step.0, -step.1), min, max, &mut emit);
+
+                if min == 0 {
+                    grid.walk(first, step, 1, Some(gcd - 1), &mut emit);
+                }
+            }
+
+            Rule::Segment => {
+                if delta.0 % 3 != 0 || delta.1 % 3 != 0 {
+                    return;
+                }
+
+                let third = (delta.0 / 3, delta.1 / 3);
+                grid.walk(first, third, 1, Some(2), &mut emit);
+            }
         }
     }
+}
 
-    fn inverted_distanced_point(&self, first: usize, second: usize) -> Option<usize> {
-        let first = self.offset_to_point(first).expect("Invalid offset");
-        let second = self.offset_to_point(second).expect("Invalid offset");
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
 
-        let x = if first.0 < second.0 {
-            let x = second.0 - first.0;
-            if x > first.0 {
-                return None;
-            }
-            first.0 - x
-        } else {
-            first.0 * 2 - second.0
+fn lcm(a: usize, b: usize) -> usize {
+    a / gcd(a, b) * b
+}
+
+type Point = (isize, isize);
+
+/// The input map. Antennas are always located by their offset within it, but
+/// antinodes are located within the topology's window.
+struct Grid {
+    columns: usize,
+    rows: usize,
+    topology: Topology,
+}
+
+/// How points beyond the edges of the input map are treated.
+#[derive(Clone, Copy, Debug)]
+enum Topology {
+    /// Points off the map are dropped, as in the puzzle.
+    Bounded,
+    /// The map wraps around at every edge.
+    Toroidal,
+    /// The map is a patch of an infinite plane, of which only the points
+    /// inside the window are kept.
+    Unbounded(Window),
+}
+
+/// A rectangle of the plane, positioned relative to the input map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Window {
+    left: isize,
+    top: isize,
+    columns: usize,
+    rows: usize,
+}
+
+impl Window {
+    fn point_to_offset(&self, point: Point) -> Option<usize> {
+        let x = usize::try_from(point.0 - self.left)
+            .ok()
+            .filter(|x| *x < self.columns)?;
+        let y = usize::try_from(point.1 - self.top)
+            .ok()
+            .filter(|y| *y < self.rows)?;
+        Some(y * self.columns + x)
+    }
+
+    /// Whether a point outside the window will never enter it by moving
+    /// along `step`.
+    fn is_past(&self, point: Point, step: Point) -> bool {
+        let past = |pos: isize, step: isize, start: isize, len: usize| {
+            (pos < start && step <= 0) || (pos >= start + len as isize && step >= 0)
         };
 
-        let y = if first.1 < second.1 {
-            let y = second.1 - first.1;
-            if y > first.1 {
-                return None;
-            }
-            first.1 - y
-        } else {
-            first.1 * 2 - second.1
+        past(point.0, step.0, self.left, self.columns) || past(point.1, step.1, self.top, self.rows)
+    }
+}
+
+impl std::str::FromStr for Window {
+    type Err = &'static str;
+
+    /// Parses `left,top,columns,rows`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',').map(str::trim);
+        let mut next = || parts.next().ok_or("Missing window dimension");
+
+        let window = Window {
+            left: next()?.parse().map_err(|_| "Invalid window left")?,
+            top: next()?.parse().map_err(|_| "Invalid window top")?,
+            columns: next()?.parse().map_err(|_| "Invalid window columns")?,
+            rows: next()?.parse().map_err(|_| "Invalid window rows")?,
         };
 
-        if x >= self.columns || y >= self.rows {
-            return None;
+        match parts.next() {
+            Some(_) => Err("Too many window dimensions"),
+            None if window.columns == 0 || window.rows == 0 => Err("Empty window"),
+            None => Ok(window),
         }
+    }
+}
 
-        self.point_to_offset((x, y))
+impl Grid {
+    /// The region antinode offsets are relative to.
+    fn window(&self) -> Window {
+        match self.topology {
+            Topology::Bounded | Topology::Toroidal => Window {
+                left: 0,
+                top: 0,
+                columns: self.columns,
+                rows: self.rows,
+            },
+            Topology::Unbounded(window) => window,
+        }
+    }
+
+    fn offset_to_point(&self, offset: usize) -> Option<Point> {
+        match offset {
+            offset if offset < self.columns * self.rows => Some((
+                (offset % self.columns) as isize,
+                (offset / self.columns) as isize,
+            )),
+            _ => None,
+        }
+    }
+
+    fn point_to_offset(&self, point: Point) -> Option<usize> {
+        match self.topology {
+            Topology::Toroidal => {
+                let x = point.0.rem_euclid(self.columns as isize) as usize;
+                let y = point.1.rem_euclid(self.rows as isize) as usize;
+                Some(y * self.columns + x)
+            }
+            _ => self.window().point_to_offset(point),
+        }
+    }
+
+    /// Emits `start + k * step` for each `k` from `min` up to `max`, stopping
+    /// early once the walk has left the window for good, or on a torus once
+    /// it starts repeating itself.
+    fn walk(
+        &self,
+        start: Point,
+        step: Point,
+        min: usize,
+        max: Option<usize>,
+        emit: &mut impl FnMut(usize),
+    ) {
+        let mut max = max.unwrap_or(usize::MAX);
+        if let Topology::Toroidal = self.topology {
+            let period = |len: usize, step: isize| len / gcd(len, step.unsigned_abs());
+            let period = lcm(period(self.columns, step.0), period(self.rows, step.1));
+            max = max.min(min.saturating_add(period - 1));
+        }
+
+        let window = self.window();
+        for k in (min..=max).map(|k| k as isize) {
+            let point = (start.0 + k * step.0, start.1 + k * step.1);
+            match self.point_to_offset(point) {
+                Some(offset) => emit(offset),
+                None if window.is_past(point, step) => break,
+                None => {}
+            }
+        }
     }
 }
 
@@ -153,18 +502,29 @@ const DIGITS_LEN: usize = len().0;
 const LOWER_LEN: usize = len().1;
 const TOTAL_LEN: usize = len().2;
 
-fn index(byte: u8) -> usize {
+fn index(byte: u8) -> Option<usize> {
     if byte.is_ascii_digit() {
-        byte as usize - const { b'0' as usize }
+        Some(byte as usize - const { b'0' as usize })
     } else if byte.is_ascii_lowercase() {
-        byte as usize - const { b'a' as usize - DIGITS_LEN }
+        Some(byte as usize - const { b'a' as usize - DIGITS_LEN })
     } else if byte.is_ascii_uppercase() {
-        byte as usize - const { b'A' as usize - LOWER_LEN }
+        Some(byte as usize - const { b'A' as usize - LOWER_LEN })
     } else {
-        panic!("Invalid byte: {}", byte);
+        None
     }
 }
 
+fn label(idx: usize) -> char {
+    let byte = if idx < DIGITS_LEN {
+        idx + b'0' as usize
+    } else if idx < LOWER_LEN {
+        idx - DIGITS_LEN + b'a' as usize
+    } else {
+        idx - LOWER_LEN + b'A' as usize
+    };
+    byte as u8 as char
+}
+
 const fn len() -> (usize, usize, usize) {
     let mut len = 0;
     let mut curr = b'0';
@@ -191,3 +551,259 @@ const fn len() -> (usize, usize, usize) {
 
     (digits, lower, len)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r"
+        ............
+        ........0...
+        .....0......
+        .......0....
+        ....0.......
+        ......A.....
+        ............
+        ............
+        ........A...
+        .........A..
+        ............
+        ............
+    ";
+
+    fn process_str(input: &str, rule: Rule) -> usize {
+        let input = input.trim().replace(' ', "");
+        let (grid, locations) = parse(input.as_bytes());
+        antinodes(&antinodes_by_frequency(&grid, &locations, rule)).len()
+    }
+
+    #[test]
+    fn test() {
+        let harmonics = Rule::Harmonics {
+            min: 0,
+            max: None,
+            reduced: false,
+        };
+
+        assert_eq!(process_str(EXAMPLE, Rule::Exact), 14);
+        assert_eq!(process_str(EXAMPLE, harmonics), 34);
+    }
+
+    #[test]
+    fn test_rules() {
+        let input = r"
+            ..........
+            .a........
+            ..........
+            ..........
+            ....a.....
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+        ";
+
+        let range = Rule::Harmonics {
+            min: 1,
+            max: Some(2),
+            reduced: false,
+        };
+
+        let harmonics = Rule::Harmonics {
+            min: 0,
+            max: None,
+            reduced: false,
+        };
+
+        // Separated by (3, 3), so only the antinode below fits
+        assert_eq!(process_str(input, Rule::Exact), 1);
+        assert_eq!(process_str(input, range), 1);
+        assert_eq!(process_str(input, harmonics), 3);
+        assert_eq!(process_str(input, Rule::Segment), 2);
+        assert_eq!(process_str(input, Rule::COLLINEAR), 10);
+    }
+
+    #[test]
+    fn test_render() {
+        let input = EXAMPLE.trim().replace(' ', "");
+        let (grid, locations) = parse(input.as_bytes());
+        let by_frequency = antinodes_by_frequency(&grid, &locations, Rule::Exact);
+
+        let expected = r"
+            ......#....#
+            ...#....0...
+            ....#0....#.
+            ..#....0....
+            ....0....#..
+            .#....A.....
+            ...#........
+            #......#....
+            ........A...
+            .........A..
+            ..........#.
+            ..........#.
+        "
+        .trim_start()
+        .replace(' ', "");
+
+        assert_eq!(render(&grid, &locations, &by_frequency, false), expected);
+        assert_eq!(
+            table(&locations, &by_frequency),
+            "Frequency  Antennas  Antinodes\n\
+             0                 4         10\n\
+             A                 3          5\n"
+        );
+
+        let colored = render(&grid, &locations, &by_frequency, true);
+        assert!(colored.starts_with("......\x1b[38;5;9m#\x1b[0m"));
+        assert!(colored.contains("\x1b[38;5;10mA\x1b[0m"));
+    }
+
+    #[test]
+    fn test_topology() {
+        let input = r"
+            ..........
+            .a........
+            ..........
+            ..........
+            ....a.....
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+        "
+        .trim()
+        .replace(' ', "");
+
+        let harmonics = Rule::Harmonics {
+            min: 0,
+            max: None,
+            reduced: false,
+        };
+
+        let count = |topology, rule| {
+            let (mut grid, locations) = parse(input.as_bytes());
+            grid.topology = topology;
+            antinodes(&antinodes_by_frequency(&grid, &locations, rule)).len()
+        };
+
+        // (-2, -2) wraps around to (8, 8), and stepping by (3, 3) around a
+        // 10x10 torus reaches the whole diagonal
+        assert_eq!(count(Topology::Toroidal, Rule::Exact), 2);
+        assert_eq!(count(Topology::Toroidal, harmonics), 10);
+        assert_eq!(count(Topology::Toroidal, Rule::COLLINEAR), 10);
+
+        let window = "-5,-5,20,20".parse().unwrap();
+        assert_eq!(count(Topology::Unbounded(window), Rule::Exact), 2);
+        // x = y = 1 + 3k for x in -5..15
+        assert_eq!(count(Topology::Unbounded(window), harmonics), 7);
+
+        // A window away from the antennas still sees the line passing through,
+        // at (22, 22) only when stepping by the pair's separation
+        let window = "20,20,5,5".parse().unwrap();
+        assert_eq!(count(Topology::Unbounded(window), harmonics), 1);
+        assert_eq!(count(Topology::Unbounded(window), Rule::COLLINEAR), 5);
+
+        let window = Window {
+            left: 0,
+            top: 0,
+            columns: 10,
+            rows: 10,
+        };
+        assert_eq!(count(Topology::Unbounded(window), harmonics), 3);
+        assert_eq!(count(Topology::Bounded, harmonics), 3);
+    }
+
+    #[test]
+    fn test_render_window() {
+        let (mut grid, locations) = parse("a..\n.a.\n...".as_bytes());
+        grid.topology = Topology::Unbounded("-1,-1,4,4".parse().unwrap());
+
+        let by_frequency = antinodes_by_frequency(&grid, &locations, Rule::Exact);
+        assert_eq!(
+            render(&grid, &locations, &by_frequency, false),
+            "#...\n.a..\n..a.\n...#\n"
+        );
+
+        assert_eq!("1,2,3".parse::<Window>(), Err("Missing window dimension"));
+        assert_eq!(
+            "1,2,3,4,5".parse::<Window>(),
+            Err("Too many window dimensions")
+        );
+        assert_eq!("0,0,0,5".parse::<Window>(), Err("Empty window"));
+        assert_eq!("0,0,5,0".parse::<Window>(), Err("Empty window"));
+    }
+
+    #[test]
+    fn test_unicode() {
+        let input = "α.β.α\n.....\n+.β.+\n.....\nα...+";
+
+        let (grid, locations) = parse(input.as_bytes());
+        let frequencies = locations
+            .iter()
+            .filter(|(_, location)| !location.is_empty())
+            .map(|(frequency, location)| (frequency, location.to_vec()))
+            .collect::<Vec<_>>();
+
+        assert_eq!((grid.columns, grid.rows), (5, 5));
+        assert_eq!(
+            frequencies,
+            [
+                ('+', vec![10, 14, 24]),
+                ('α', vec![0, 4, 20]),
+                ('β', vec![2, 12])
+            ]
+        );
+
+        let collinear = antinodes(&antinodes_by_frequency(&grid, &locations, Rule::COLLINEAR));
+        assert_eq!(collinear.len(), 21);
+    }
+
+    #[test]
+    fn test_labels() {
+        for byte in (b'0'..=b'9').chain(b'a'..=b'z').chain(b'A'..=b'Z') {
+            assert_eq!(label(index(byte).unwrap()), byte as char);
+        }
+        assert_eq!(index(b'#'), None);
+    }
+
+    #[test]
+    fn test_reduced() {
+        // Separated by (2, 4), so stepping by the pair's separation skips the
+        // cells at (2, 3) and (4, 7) on the line between and beyond them
+        let input = r"
+            .........
+            .b.......
+            .........
+            .........
+            .........
+            ...b.....
+            .........
+            .........
+            .........
+        ";
+
+        let pair = Rule::Harmonics {
+            min: 0,
+            max: None,
+            reduced: false,
+        };
+        let reduced = Rule::Harmonics {
+            min: 0,
+            max: None,
+            reduced: true,
+        };
+        let reduced_range = Rule::Harmonics {
+            min: 1,
+            max: Some(2),
+            reduced: true,
+        };
+
+        assert_eq!(process_str(input, pair), 2);
+        assert_eq!(process_str(input, reduced), 4);
+        assert_eq!(process_str(input, Rule::Exact), 0);
+        assert_eq!(process_str(input, reduced_range), 1);
+    }
+}
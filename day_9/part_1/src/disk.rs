@@ -0,0 +1,285 @@
+use std::fmt;
+
+use crate::format;
+
+/// A way of moving files towards the front of the disk.
+pub trait CompactionStrategy {
+    fn name(&self) -> &'static str;
+
+    /// Rearranges the blocks, returning how many moves were made.
+    fn compact(&self, blocks: &mut [Option<usize>]) -> usize;
+}
+
+/// Moves single blocks from the end of the disk into the leftmost free block,
+/// splitting files up as needed. Each block moved counts as a move.
+pub struct Fragment;
+
+/// Moves each file whole, in decreasing id order, into the leftmost free span
+/// to its left that fits it, or leaves it where it is.
+pub struct FirstFit;
+
+/// Like [`FirstFit`], but picks the smallest span that fits.
+pub struct BestFit;
+
+/// Like [`FirstFit`], but picks the largest span that fits.
+pub struct WorstFit;
+
+/// Packs every file whole and in order at the front of the disk, leaving a
+/// single free span at the end. Each file that changes position counts as a
+/// move.
+pub struct Defragment;
+
+impl CompactionStrategy for Fragment {
+    fn name(&self) -> &'static str {
+        "fragment"
+    }
+
+    fn compact(&self, blocks: &mut [Option<usize>]) -> usize {
+        let mut moves = 0;
+        let mut free = 0;
+        let mut tail = blocks.len();
+
+        loop {
+            while free < tail && blocks[free].is_some() {
+                free += 1;
+            }
+            while tail > free && blocks[tail - 1].is_none() {
+                tail -= 1;
+            }
+            if tail <= free {
+                break;
+            }
+
+            blocks.swap(free, tail - 1);
+            moves += 1;
+        }
+
+        moves
+    }
+}
+
+impl CompactionStrategy for FirstFit {
+    fn name(&self) -> &'static str {
+        "first-fit"
+    }
+
+    fn compact(&self, blocks: &mut [Option<usize>]) -> usize {
+        move_files(blocks, |spans| spans.first().copied())
+    }
+}
+
+impl CompactionStrategy for BestFit {
+    fn name(&self) -> &'static str {
+        "best-fit"
+    }
+
+    fn compact(&self, blocks: &mut [Option<usize>]) -> usize {
+        move_files(blocks, |spans| {
+            spans
+                .iter()
+                .copied()
+                .min_by_key(|(start, len)| (*len, *start))
+        })
+    }
+}
+
+impl CompactionStrategy for WorstFit {
+    fn name(&self) -> &'static str {
+        "worst-fit"
+    }
+
+    fn compact(&self, blocks: &mut [Option<usize>]) -> usize {
+        move_files(blocks, |spans| {
+            spans
+                .iter()
+                .copied()
+                .min_by_key(|(start, len)| (std::cmp::Reverse(*len), *start))
+        })
+    }
+}
+
+impl CompactionStrategy for Defragment {
+    fn name(&self) -> &'static str {
+        "defragment"
+    }
+
+    fn compact(&self, blocks: &mut [Option<usize>]) -> usize {
+        let mut order = Vec::new();
+        let mut sizes = Vec::new();
+        let mut starts = Vec::new();
+
+        for (i, block) in blocks.iter().enumerate() {
+            if let Some(id) = *block {
+                if id >= sizes.len() {
+                    sizes.resize(id + 1, 0);
+                    starts.resize(id + 1, None);
+                }
+                if starts[id].is_none() {
+                    starts[id] = Some(i);
+                    order.push(id);
+                }
+                sizes[id] += 1;
+            }
+        }
+
+        let mut moves = 0;
+        let mut offset = 0;
+
+        for id in order {
+            let size = sizes[id];
+            let in_place = blocks[offset..offset + size]
+                .iter()
+                .all(|block| *block == Some(id));
+            if starts[id] != Some(offset) || !in_place {
+                moves += 1;
+            }
+
+            blocks[offset..offset + size].fill(Some(id));
+            offset += size;
+        }
+
+        blocks[offset..].fill(None);
+        moves
+    }
+}
+
+/// Moves each file whole, in decreasing id order, into the free span `choose`
+/// picks among the `(start, len)` spans left of it that are large enough.
+fn move_files(
+    blocks: &mut [Option<usize>],
+    choose: impl Fn(&[(usize, usize)]) -> Option<(usize, usize)>,
+) -> usize {
+    let mut files = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        if let Some(id) = *block {
+            if id >= files.len() {
+                files.resize(id + 1, (i, 0));
+            }
+            files[id].1 += 1;
+        }
+    }
+
+    let mut moves = 0;
+
+    for (id, (start, size)) in files.into_iter().enumerate().rev() {
+        if size == 0 {
+            continue;
+        }
+
+        let spans = free_spans(&blocks[..start])
+            .filter(|(_, len)| *len >= size)
+            .collect::<Vec<_>>();
+
+        if let Some((dest, _)) = choose(&spans) {
+            blocks[dest..dest + size].fill(Some(id));
+            blocks[start..start + size].fill(None);
+            moves += 1;
+        }
+    }
+
+    moves
+}
+
+/// The `(start, len)` runs of free blocks, from left to right.
+fn free_spans(blocks: &[Option<usize>]) -> impl '_ + Iterator<Item = (usize, usize)> {
+    let mut i = 0;
+    std::iter::from_fn(move || {
+        while i < blocks.len() && blocks[i].is_some() {
+            i += 1;
+        }
+
+        let start = i;
+        while i < blocks.len() && blocks[i].is_none() {
+            i += 1;
+        }
+
+        (i > start).then_some((start, i - start))
+    })
+}
+
+/// The outcome of compacting a disk.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Report {
+    pub checksum: usize,
+    pub moves: usize,
+    /// How many separate runs of free blocks are left.
+    pub free_spans: usize,
+    /// The share of free blocks outside the largest free span, from 0 when
+    /// all free space is in one piece towards 1.
+    pub fragmentation: f64,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Checksum: {}", self.checksum)?;
+        writeln!(f, "Moves: {}", self.moves)?;
+        writeln!(f, "Free spans: {}", self.free_spans)?;
+        write!(f, "Fragmentation: {:.3}", self.fragmentation)
+    }
+}
+
+/// A disk with every block materialised, either holding part of a file or
+/// free.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiskMap {
+    blocks: Vec<Option<usize>>,
+}
+
+impl DiskMap {
+    /// Parses a disk map in any of the supported formats, where sizes
+    /// alternate between a file and the free space after it.
+    pub fn parse(input: &[u8]) -> Self {
+        let mut blocks = Vec::new();
+
+        for (i, size) in format::decode(input).into_iter().enumerate() {
+            let block = if i % 2 == 0 { Some(i / 2) } else { None };
+            blocks.extend(std::iter::repeat_n(block, size));
+        }
+
+        Self { blocks }
+    }
+
+    pub fn checksum(&self) -> usize {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, block)| block.map(|id| i * id))
+            .sum()
+    }
+
+    pub fn compact(&mut self, strategy: &dyn CompactionStrategy) -> Report {
+        let moves = strategy.compact(&mut self.blocks);
+
+        let (free_spans, total, largest) = free_spans(&self.blocks)
+            .fold((0, 0, 0), |(count, total, largest), (_, len)| {
+                (count + 1, total + len, largest.max(len))
+            });
+
+        let fragmentation = match total {
+            0 => 0.0,
+            total => 1.0 - largest as f64 / total as f64,
+        };
+
+        Report {
+            checksum: self.checksum(),
+            moves,
+            free_spans,
+            fragmentation,
+        }
+    }
+}
+
+/// Renders the disk as in the puzzle, with `.` for free blocks and each file
+/// block as its id. Ids past 9 are bracketed so they stay unambiguous.
+impl fmt::Display for DiskMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for block in &self.blocks {
+            match block {
+                None => write!(f, ".")?,
+                Some(id) if *id < 10 => write!(f, "{id}")?,
+                Some(id) => write!(f, "[{id}]")?,
+            }
+        }
+        Ok(())
+    }
+}
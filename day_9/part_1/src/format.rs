@@ -0,0 +1,119 @@
+//! Encodings of a disk map: a list of sizes alternating between a file and
+//! the free space after it.
+
+/// Marks the start of a varint-encoded disk map. It can't start a text
+/// format, which is how the formats are told apart.
+const VARINT_MAGIC: u8 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// The puzzle's format, one digit per size.
+    Dense,
+    /// Decimal sizes separated by commas, for sizes past 9. A lone size
+    /// needs a trailing comma to be told apart from the dense format.
+    Comma,
+    /// A marker byte, then each size as an unsigned LEB128 varint.
+    Varint,
+}
+
+impl Format {
+    pub fn detect(input: &[u8]) -> Self {
+        match input {
+            [VARINT_MAGIC, ..] => Format::Varint,
+            _ if input.contains(&b',') => Format::Comma,
+            _ => Format::Dense,
+        }
+    }
+
+    pub fn decode(self, input: &[u8]) -> Vec<usize> {
+        match self {
+            Format::Dense => input
+                .trim_ascii()
+                .iter()
+                .map(|b| {
+                    assert!(b.is_ascii_digit());
+                    (b - b'0') as usize
+                })
+                .collect(),
+
+            Format::Comma => input
+                .trim_ascii()
+                .split(|b| *b == b',')
+                .map(|size| size.trim_ascii())
+                .filter(|size| !size.is_empty())
+                .map(|size| {
+                    std::str::from_utf8(size)
+                        .ok()
+                        .and_then(|size| size.parse().ok())
+                        .expect("Cannot parse size")
+                })
+                .collect(),
+
+            Format::Varint => {
+                let mut sizes = Vec::new();
+                let mut size = 0usize;
+                let mut shift = 0;
+
+                for b in &input[1..] {
+                    let bits = (*b & 0x7f) as usize;
+                    size |= bits
+                        .checked_shl(shift)
+                        .filter(|shifted| shifted >> shift == bits)
+                        .expect("Size too large");
+                    shift += 7;
+
+                    if b & 0x80 == 0 {
+                        sizes.push(size);
+                        size = 0;
+                        shift = 0;
+                    }
+                }
+
+                assert_eq!(shift, 0, "Truncated varint");
+                sizes
+            }
+        }
+    }
+
+    pub fn encode(self, sizes: &[usize]) -> Vec<u8> {
+        match self {
+            Format::Dense => sizes
+                .iter()
+                .map(|size| {
+                    assert!(*size < 10, "Size too large for the dense format");
+                    b'0' + *size as u8
+                })
+                .collect(),
+
+            Format::Comma => {
+                let mut out = sizes
+                    .iter()
+                    .map(|size| size.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                if sizes.len() == 1 {
+                    out.push(',');
+                }
+                out.into_bytes()
+            }
+
+            Format::Varint => {
+                let mut out = vec![VARINT_MAGIC];
+                for &size in sizes {
+                    let mut size = size;
+                    while size >= 0x80 {
+                        out.push(size as u8 | 0x80);
+                        size >>= 7;
+                    }
+                    out.push(size as u8);
+                }
+                out
+            }
+        }
+    }
+}
+
+/// Decodes a disk map in whichever format it is in.
+pub fn decode(input: &[u8]) -> Vec<usize> {
+    Format::detect(input).decode(input)
+}
@@ -1,33 +1,89 @@
-use std::{
-    fs::File,
-    io::{BufReader, Read},
-};
+use std::io::{BufRead, Write};
+
+use disk::{BestFit, CompactionStrategy, Defragment, DiskMap, FirstFit, Fragment, WorstFit};
+
+use format::Format;
+
+mod disk;
+mod format;
+
+const STRATEGIES: &[&dyn CompactionStrategy] =
+    &[&Fragment, &FirstFit, &BestFit, &WorstFit, &Defragment];
 
 fn main() {
-    let path = std::env::args()
-        .nth(1)
-        .expect("No input file path provided");
-    let file = File::open(path).expect("Cannot open file");
-    let reader = BufReader::new(file);
+    let mut path = None;
+    let mut render = false;
+    let mut strategy = None;
+    let mut encode = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--render" => render = true,
+            "--strategy" => strategy = Some(args.next().expect("No strategy provided")),
+            "--encode" => encode = Some(args.next().expect("No format provided")),
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("Unexpected argument: {arg}"),
+        }
+    }
+
+    let path = path.expect("No input file path provided");
+    let input = std::fs::read(path).expect("Cannot read file");
+
+    if let Some(encode) = encode {
+        let format = match encode.as_str() {
+            "dense" => Format::Dense,
+            "comma" => Format::Comma,
+            "varint" => Format::Varint,
+            _ => panic!("Unknown format: {encode}"),
+        };
+
+        let encoded = format.encode(&format::decode(&input));
+        std::io::stdout()
+            .write_all(&encoded)
+            .expect("Cannot write output");
+        return;
+    }
+
+    let strategies = match strategy.as_deref() {
+        None if !render => {
+            println!("{}", process(input.as_slice()));
+            return;
+        }
+        None => vec![&Fragment as &dyn CompactionStrategy],
+        Some("all") => STRATEGIES.to_vec(),
+        Some(name) => {
+            let strategy = STRATEGIES.iter().find(|strategy| strategy.name() == name);
+            vec![*strategy.unwrap_or_else(|| panic!("Unknown strategy: {name}"))]
+        }
+    };
+
+    for strategy in strategies {
+        let mut disk = DiskMap::parse(&input);
+        if render {
+            println!("{disk}");
+        }
 
-    let sum = process(reader);
+        let report = disk.compact(strategy);
+        if render {
+            println!("{disk}");
+        }
 
-    println!("{sum}");
+        println!("Strategy: {}", strategy.name());
+        println!("{report}");
+        println!();
+    }
 }
 
-fn process(reader: impl Read) -> usize {
-    let mut bytes = reader
-        .bytes()
-        .map(|byte| byte.expect("Cannot read byte"))
-        .map(|b| {
-            assert!(b.is_ascii_digit());
-            (b - b'0') as usize
-        });
+fn process(mut reader: impl BufRead) -> usize {
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input).expect("Cannot read input");
+    let mut sizes = format::decode(&input).into_iter();
 
     let mut disk = Vec::new();
 
-    while let Some(size) = bytes.next() {
-        let free = bytes.next().unwrap_or(0);
+    while let Some(size) = sizes.next() {
+        let free = sizes.next().unwrap_or(0);
         disk.push((size, free));
     }
 
@@ -96,4 +152,132 @@ mod test {
 
         assert_eq!(sum, 1928);
     }
+
+    #[test]
+    fn test_disk_map() {
+        let input = "2333133121414131402";
+        let mut disk = DiskMap::parse(input.as_bytes());
+
+        assert_eq!(
+            disk.to_string(),
+            "00...111...2...333.44.5555.6666.777.888899"
+        );
+
+        let mut fragmented = disk.clone();
+        fragmented.compact(&Fragment);
+        assert_eq!(
+            fragmented.to_string(),
+            "0099811188827773336446555566.............."
+        );
+        assert_eq!(fragmented.checksum(), 1928);
+
+        disk.compact(&FirstFit);
+        assert_eq!(
+            disk.to_string(),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+        assert_eq!(disk.checksum(), 2858);
+    }
+
+    #[test]
+    fn test_disk_map_ids() {
+        let input = "10101010101010101010101";
+        let disk = DiskMap::parse(input.as_bytes());
+
+        assert_eq!(disk.to_string(), "0123456789[10][11]");
+        assert_eq!(disk.checksum(), process(input.as_bytes()));
+    }
+
+    #[test]
+    fn test_disk_map_empty_file() {
+        // File 1 takes no space at all
+        let input = "12022";
+        let mut disk = DiskMap::parse(input.as_bytes());
+        assert_eq!(disk.to_string(), "0....22");
+
+        disk.compact(&FirstFit);
+        assert_eq!(disk.to_string(), "022....");
+    }
+
+    #[test]
+    fn test_strategies() {
+        let input = "2333133121414131402";
+
+        let compact = |strategy: &dyn CompactionStrategy| {
+            let mut disk = DiskMap::parse(input.as_bytes());
+            let report = disk.compact(strategy);
+            (disk.to_string(), report)
+        };
+
+        let (layout, report) = compact(&Fragment);
+        assert_eq!(layout, "0099811188827773336446555566..............");
+        assert_eq!((report.checksum, report.moves), (1928, 12));
+        assert_eq!((report.free_spans, report.fragmentation), (1, 0.0));
+
+        let (layout, report) = compact(&FirstFit);
+        assert_eq!(layout, "00992111777.44.333....5555.6666.....8888..");
+        assert_eq!((report.checksum, report.moves), (2858, 4));
+        assert_eq!(report.free_spans, 6);
+        assert_eq!(report.fragmentation, 1.0 - 5.0 / 14.0);
+
+        // Every span that fits is the same size here, so best-fit agrees
+        let (layout, report) = compact(&BestFit);
+        assert_eq!(layout, "00992111777.44.333....5555.6666.....8888..");
+        assert_eq!((report.checksum, report.moves), (2858, 4));
+
+        let (layout, report) = compact(&Defragment);
+        assert_eq!(layout, "0011123334455556666777888899..............");
+        assert_eq!(report.moves, 9);
+        assert_eq!((report.free_spans, report.fragmentation), (1, 0.0));
+    }
+
+    #[test]
+    fn test_fits() {
+        let input = "13111";
+
+        let compact = |strategy: &dyn CompactionStrategy| {
+            let mut disk = DiskMap::parse(input.as_bytes());
+            let report = disk.compact(strategy);
+            (disk.to_string(), report.moves)
+        };
+
+        assert_eq!(DiskMap::parse(input.as_bytes()).to_string(), "0...1.2");
+        assert_eq!(compact(&FirstFit), ("021....".to_string(), 2));
+        assert_eq!(compact(&BestFit), ("01...2.".to_string(), 2));
+        assert_eq!(compact(&WorstFit), ("021....".to_string(), 2));
+        assert_eq!(compact(&Defragment), ("012....".to_string(), 2));
+    }
+
+    #[test]
+    fn test_formats() {
+        for dense in ["2333133121414131402", "9"] {
+            let sizes = format::decode(dense.as_bytes());
+
+            for format in [Format::Dense, Format::Comma, Format::Varint] {
+                let encoded = format.encode(&sizes);
+                assert_eq!(Format::detect(&encoded), format);
+                assert_eq!(format::decode(&encoded), sizes);
+                assert_eq!(process(encoded.as_slice()), process(dense.as_bytes()));
+            }
+        }
+
+        assert_eq!(Format::Comma.encode(&[12]), b"12,");
+        assert_eq!(format::decode(&Format::Comma.encode(&[12])), [12]);
+
+        assert_eq!(format::decode(b"2,3,3\n"), [2, 3, 3]);
+        assert_eq!(format::decode(b"12,"), [12]);
+        assert_eq!(format::decode(b"12\n"), [1, 2]);
+    }
+
+    #[test]
+    fn test_large_sizes() {
+        let sizes = [12, 300, 1, 0, 70000, 2, 128];
+        let encoded = Format::Varint.encode(&sizes);
+        assert_eq!(&encoded[..4], [0, 12, 0xac, 0x02]);
+        assert_eq!(format::decode(&encoded), sizes);
+
+        let disk = DiskMap::parse(b"12,3,2");
+        assert_eq!(disk.to_string(), "000000000000...11");
+        assert_eq!(process(b"12, 3, 2".as_slice()), 12 + 13);
+    }
 }
@@ -1,38 +1,123 @@
 use std::{
-    fs::File,
-    io::{BufReader, Read},
+    io::{BufRead, Write},
+    time::{Duration, Instant},
 };
 
+use disk::{BestFit, CompactionStrategy, Defragment, DiskMap, FirstFit, Fragment, WorstFit};
+
+use format::Format;
+
+mod disk;
+mod format;
+
+const STRATEGIES: &[&dyn CompactionStrategy] =
+    &[&Fragment, &FirstFit, &BestFit, &WorstFit, &Defragment];
+
 fn main() {
-    let path = std::env::args()
-        .nth(1)
-        .expect("No input file path provided");
-    let file = File::open(path).expect("Cannot open file");
-    let reader = BufReader::new(file);
+    let mut path = None;
+    let mut render = false;
+    let mut strategy = None;
+    let mut encode = None;
+    let mut bench = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--render" => render = true,
+            "--strategy" => strategy = Some(args.next().expect("No strategy provided")),
+            "--bench" => bench = Some(args.next().expect("No disk map length provided")),
+            "--encode" => encode = Some(args.next().expect("No format provided")),
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("Unexpected argument: {arg}"),
+        }
+    }
+
+    if let Some(len) = bench {
+        let len = len.parse().expect("Cannot parse disk map length");
+        return benchmark(len);
+    }
+
+    let path = path.expect("No input file path provided");
+    let input = std::fs::read(path).expect("Cannot read file");
+
+    if let Some(encode) = encode {
+        let format = match encode.as_str() {
+            "dense" => Format::Dense,
+            "comma" => Format::Comma,
+            "varint" => Format::Varint,
+            _ => panic!("Unknown format: {encode}"),
+        };
+
+        let encoded = format.encode(&format::decode(&input));
+        std::io::stdout()
+            .write_all(&encoded)
+            .expect("Cannot write output");
+        return;
+    }
+
+    let strategies = match strategy.as_deref() {
+        None if !render => {
+            println!("{}", process(input.as_slice()));
+            return;
+        }
+        None => vec![&FirstFit as &dyn CompactionStrategy],
+        Some("all") => STRATEGIES.to_vec(),
+        Some(name) => {
+            let strategy = STRATEGIES.iter().find(|strategy| strategy.name() == name);
+            vec![*strategy.unwrap_or_else(|| panic!("Unknown strategy: {name}"))]
+        }
+    };
 
-    let sum = process(reader);
+    for strategy in strategies {
+        let mut disk = DiskMap::parse(&input);
+        if render {
+            println!("{disk}");
+        }
+
+        let report = disk.compact(strategy);
+        if render {
+            println!("{disk}");
+        }
+
+        println!("Strategy: {}", strategy.name());
+        println!("{report}");
+        println!();
+    }
+}
 
-    println!("{sum}");
+fn process(reader: impl BufRead) -> usize {
+    compact_tree(&parse(reader))
 }
 
-fn process(reader: impl Read) -> usize {
-    let mut bytes = reader
-        .bytes()
-        .map(|byte| byte.expect("Cannot read byte"))
-        .map(|b| {
-            assert!(b.is_ascii_digit());
-            (b - b'0') as usize
-        });
+/// Reads the dense format into `(size, free)` pairs, one per file.
+fn parse(mut reader: impl BufRead) -> Vec<(usize, usize)> {
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input).expect("Cannot read input");
+    let mut sizes = format::decode(&input).into_iter();
 
     let mut disk = Vec::new();
 
-    let mut offset = 0;
-    while let Some(size) = bytes.next() {
-        let free = bytes.next().unwrap_or(0);
-        disk.push((size, free, 0, offset));
-        offset += size + free;
+    while let Some(size) = sizes.next() {
+        let free = sizes.next().unwrap_or(0);
+        disk.push((size, free));
     }
 
+    disk
+}
+
+/// Whole-file compaction that searches every free span from the left for
+/// each file, in quadratic time.
+fn compact_scan(disk: &[(usize, usize)]) -> usize {
+    let mut offset = 0;
+    let mut disk = disk
+        .iter()
+        .map(|&(size, free)| {
+            let file = (size, free, 0, offset);
+            offset += size + free;
+            file
+        })
+        .collect::<Vec<_>>();
+
     let mut sum = 0;
     let tail = disk.len() - 1;
 
@@ -40,9 +125,7 @@ fn process(reader: impl Read) -> usize {
         let (front, back) = disk.split_at_mut(tail);
         let (back_size, _, _, _) = &mut back[0];
 
-        for i in 0..tail {
-            let (front_size, free, used, offset) = &mut front[i];
-
+        for (front_size, free, used, offset) in front.iter_mut() {
             if back_size > free {
                 continue;
             }
@@ -68,6 +151,148 @@ fn process(reader: impl Read) -> usize {
     sum
 }
 
+/// Whole-file compaction that keeps the length of the free span after each
+/// file in a [`SpanTree`]. Finding the leftmost span a file fits in and
+/// shrinking it both take `O(log n)`, so the whole pass takes `O(n log n)`
+/// whatever the sizes.
+///
+/// Spans never grow: the space a file leaves behind is to the right of every
+/// file still to be moved, so it can't be used and is not tracked.
+fn compact_tree(disk: &[(usize, usize)]) -> usize {
+    let mut offsets = Vec::with_capacity(disk.len());
+    let mut spans = Vec::with_capacity(disk.len());
+
+    let mut offset = 0;
+    for &(size, free) in disk {
+        offsets.push(offset);
+        offset += size;
+        spans.push(offset);
+        offset += free;
+    }
+
+    let mut tree = SpanTree::new(disk.iter().map(|&(_, free)| free).collect());
+    let mut sum = 0;
+
+    for (id, &(size, _)) in disk.iter().enumerate().rev() {
+        let mut start = offsets[id];
+
+        // Only spans after earlier files are to the left of this one
+        if let Some(span) = tree.leftmost(size).filter(|span| *span < id && size > 0) {
+            start = spans[span];
+            spans[span] += size;
+            tree.shrink(span, size);
+        }
+
+        sum += id * (size * start + size * size.saturating_sub(1) / 2);
+    }
+
+    sum
+}
+
+/// A segment tree over the lengths of the free spans, in disk order, where
+/// each node holds the longest span below it.
+struct SpanTree {
+    leaves: usize,
+    longest: Vec<usize>,
+}
+
+impl SpanTree {
+    fn new(lens: Vec<usize>) -> Self {
+        let leaves = lens.len().next_power_of_two();
+        let mut longest = vec![0; 2 * leaves];
+        longest[leaves..leaves + lens.len()].copy_from_slice(&lens);
+
+        for node in (1..leaves).rev() {
+            longest[node] = longest[2 * node].max(longest[2 * node + 1]);
+        }
+
+        Self { leaves, longest }
+    }
+
+    /// The first span at least `size` long, found by going left from the
+    /// root whenever the left child has one.
+    fn leftmost(&self, size: usize) -> Option<usize> {
+        if self.longest[1] < size {
+            return None;
+        }
+
+        let mut node = 1;
+        while node < self.leaves {
+            node = match self.longest[2 * node] >= size {
+                true => 2 * node,
+                false => 2 * node + 1,
+            };
+        }
+
+        Some(node - self.leaves)
+    }
+
+    fn shrink(&mut self, span: usize, size: usize) {
+        let mut node = span + self.leaves;
+        self.longest[node] -= size;
+
+        while node > 1 {
+            node /= 2;
+            self.longest[node] = self.longest[2 * node].max(self.longest[2 * node + 1]);
+        }
+    }
+}
+
+/// Generates a random disk map with `len` sizes in `format`, using a fixed
+/// seed so runs are comparable. Sizes go up to 9 in the dense format and up
+/// to 999 in the others.
+fn generate(len: usize, format: Format) -> Vec<u8> {
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let max = match format {
+        Format::Dense => 9,
+        _ => 999,
+    };
+
+    let sizes = (0..len)
+        .map(|i| match i % 2 {
+            0 => 1 + (next() % max) as usize,
+            _ => (next() % (max + 1)) as usize,
+        })
+        .collect::<Vec<_>>();
+
+    format.encode(&sizes)
+}
+
+/// Times both compaction paths on generated disk maps of growing length up
+/// to `max_len`, with single digit and wider sizes, checking they agree.
+fn benchmark(max_len: usize) {
+    fn time(f: impl FnOnce() -> usize) -> (usize, Duration) {
+        let start = Instant::now();
+        let sum = f();
+        (sum, start.elapsed())
+    }
+
+    let mut len = 1000.min(max_len);
+    loop {
+        for (name, format) in [("dense", Format::Dense), ("comma", Format::Comma)] {
+            let disk = parse(generate(len, format).as_slice());
+
+            let (scan, scan_time) = time(|| compact_scan(&disk));
+            let (tree, tree_time) = time(|| compact_tree(&disk));
+            assert_eq!(scan, tree, "Checksums differ for {name} length {len}");
+
+            println!("{len:>9} {name} sizes: scan {scan_time:>12.3?}, tree {tree_time:>12.3?}");
+        }
+
+        if len >= max_len {
+            break;
+        }
+        len = (len * 4).min(max_len);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -80,4 +305,170 @@ mod test {
 
         assert_eq!(sum, 2858);
     }
+
+    #[test]
+    fn test_compact_tree() {
+        for len in [1, 2, 3, 10, 101, 1000, 5000] {
+            let disk = parse(generate(len, Format::Dense).as_slice());
+            assert_eq!(compact_tree(&disk), compact_scan(&disk), "length {len}");
+
+            let mut map = DiskMap::parse(&generate(len, Format::Dense));
+            map.compact(&FirstFit);
+            assert_eq!(compact_tree(&disk), map.checksum(), "length {len}");
+        }
+
+        for len in [1, 2, 3, 10, 101, 1000, 5000] {
+            let input = generate(len, Format::Comma);
+            let disk = parse(input.as_slice());
+            assert_eq!(compact_tree(&disk), compact_scan(&disk), "length {len}");
+            assert_eq!(
+                compact_tree(&parse(generate(len, Format::Varint).as_slice())),
+                compact_tree(&disk),
+                "length {len}"
+            );
+
+            if len <= 101 {
+                let mut map = DiskMap::parse(&input);
+                map.compact(&FirstFit);
+                assert_eq!(compact_tree(&disk), map.checksum(), "length {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_disk_map() {
+        let input = "2333133121414131402";
+        let mut disk = DiskMap::parse(input.as_bytes());
+
+        assert_eq!(
+            disk.to_string(),
+            "00...111...2...333.44.5555.6666.777.888899"
+        );
+
+        let mut fragmented = disk.clone();
+        fragmented.compact(&Fragment);
+        assert_eq!(
+            fragmented.to_string(),
+            "0099811188827773336446555566.............."
+        );
+        assert_eq!(fragmented.checksum(), 1928);
+
+        disk.compact(&FirstFit);
+        assert_eq!(
+            disk.to_string(),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+        assert_eq!(disk.checksum(), 2858);
+    }
+
+    #[test]
+    fn test_disk_map_ids() {
+        let input = "10101010101010101010101";
+        let disk = DiskMap::parse(input.as_bytes());
+
+        assert_eq!(disk.to_string(), "0123456789[10][11]");
+        assert_eq!(disk.checksum(), process(input.as_bytes()));
+    }
+
+    #[test]
+    fn test_disk_map_empty_file() {
+        // File 1 takes no space at all
+        let input = "12022";
+        let mut disk = DiskMap::parse(input.as_bytes());
+        assert_eq!(disk.to_string(), "0....22");
+
+        disk.compact(&FirstFit);
+        assert_eq!(disk.to_string(), "022....");
+    }
+
+    #[test]
+    fn test_strategies() {
+        let input = "2333133121414131402";
+
+        let compact = |strategy: &dyn CompactionStrategy| {
+            let mut disk = DiskMap::parse(input.as_bytes());
+            let report = disk.compact(strategy);
+            (disk.to_string(), report)
+        };
+
+        let (layout, report) = compact(&Fragment);
+        assert_eq!(layout, "0099811188827773336446555566..............");
+        assert_eq!((report.checksum, report.moves), (1928, 12));
+        assert_eq!((report.free_spans, report.fragmentation), (1, 0.0));
+
+        let (layout, report) = compact(&FirstFit);
+        assert_eq!(layout, "00992111777.44.333....5555.6666.....8888..");
+        assert_eq!((report.checksum, report.moves), (2858, 4));
+        assert_eq!(report.free_spans, 6);
+        assert_eq!(report.fragmentation, 1.0 - 5.0 / 14.0);
+
+        // Every span that fits is the same size here, so best-fit agrees
+        let (layout, report) = compact(&BestFit);
+        assert_eq!(layout, "00992111777.44.333....5555.6666.....8888..");
+        assert_eq!((report.checksum, report.moves), (2858, 4));
+
+        let (layout, report) = compact(&Defragment);
+        assert_eq!(layout, "0011123334455556666777888899..............");
+        assert_eq!(report.moves, 9);
+        assert_eq!((report.free_spans, report.fragmentation), (1, 0.0));
+    }
+
+    #[test]
+    fn test_fits() {
+        let input = "13111";
+
+        let compact = |strategy: &dyn CompactionStrategy| {
+            let mut disk = DiskMap::parse(input.as_bytes());
+            let report = disk.compact(strategy);
+            (disk.to_string(), report.moves)
+        };
+
+        assert_eq!(DiskMap::parse(input.as_bytes()).to_string(), "0...1.2");
+        assert_eq!(compact(&FirstFit), ("021....".to_string(), 2));
+        assert_eq!(compact(&BestFit), ("01...2.".to_string(), 2));
+        assert_eq!(compact(&WorstFit), ("021....".to_string(), 2));
+        assert_eq!(compact(&Defragment), ("012....".to_string(), 2));
+    }
+
+    #[test]
+    fn test_formats() {
+        for dense in ["2333133121414131402", "9"] {
+            let sizes = format::decode(dense.as_bytes());
+
+            for format in [Format::Dense, Format::Comma, Format::Varint] {
+                let encoded = format.encode(&sizes);
+                assert_eq!(Format::detect(&encoded), format);
+                assert_eq!(format::decode(&encoded), sizes);
+                assert_eq!(process(encoded.as_slice()), process(dense.as_bytes()));
+            }
+        }
+
+        assert_eq!(Format::Comma.encode(&[12]), b"12,");
+        assert_eq!(format::decode(&Format::Comma.encode(&[12])), [12]);
+
+        assert_eq!(format::decode(b"2,3,3\n"), [2, 3, 3]);
+        assert_eq!(format::decode(b"12,"), [12]);
+        assert_eq!(format::decode(b"12\n"), [1, 2]);
+    }
+
+    #[test]
+    fn test_large_sizes() {
+        let sizes = [12, 300, 1, 0, 70000, 2, 128];
+        let encoded = Format::Varint.encode(&sizes);
+        assert_eq!(&encoded[..4], [0, 12, 0xac, 0x02]);
+        assert_eq!(format::decode(&encoded), sizes);
+
+        let disk = DiskMap::parse(b"12,3,2");
+        assert_eq!(disk.to_string(), "000000000000...11");
+        assert_eq!(process(b"12, 3, 2".as_slice()), 12 + 13);
+
+        // File 2 moves into the huge span right after file 0, then file 1
+        // right after it
+        assert_eq!(process(b"1,500000000,1,3,2".as_slice()), 2 * (1 + 2) + 3);
+        let sizes = [1, 500000000, 1, 3, 2];
+        assert_eq!(
+            compact_tree(&parse(Format::Varint.encode(&sizes).as_slice())),
+            9
+        );
+    }
 }